@@ -2,8 +2,6 @@
 #[derive(Clone, Copy, Debug, Hash, PartialEq, PartialOrd)]
 pub struct Color(pub u32);
 
-const GAMMA: f32 = 2.2;
-
 impl Color {
     pub const BLACK_TRANSPARENT: Self = Self(0x0);
     pub const BLACK_OPAQUE: Self = Self(0xFF000000);
@@ -49,16 +47,26 @@ impl Color {
     pub fn into_vec4(self) -> [f32; 4] {
         [self.red(), self.green(), self.blue(), self.alpha()]
     }
-    pub fn into_vec4_gamma(self) -> [f32; 4] {
+    /// Components in the linear space, with the color seen as sRGB.
+    /// The alpha is kept as is.
+    pub fn into_vec4_linear(self) -> [f32; 4] {
         [
-            self.red().powf(GAMMA),
-            self.green().powf(GAMMA),
-            self.blue().powf(GAMMA),
-            self.alpha().powf(GAMMA),
+            srgb_to_linear(self.red()),
+            srgb_to_linear(self.green()),
+            srgb_to_linear(self.blue()),
+            self.alpha(),
         ]
     }
 }
 
+fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
 impl From<Color> for wgpu::Color {
     fn from(c: Color) -> Self {
         Self {
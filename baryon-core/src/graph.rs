@@ -0,0 +1,357 @@
+use crate::{Camera, Context, Pass, Scene, TargetRef};
+use std::any::Any;
+
+/// Texture used by the passes of a render graph.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Resource {
+    /// One of the targets the graph is drawn into, by index.
+    Target(usize),
+    /// Target owned by the context.
+    External(TargetRef),
+    /// Target allocated by the graph, only living within it.
+    Transient(usize),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum TransientSize {
+    Fixed(mint::Vector2<u32>),
+    Screen,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct TransientDesc {
+    format: wgpu::TextureFormat,
+    size: TransientSize,
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct PassId(usize);
+
+trait GraphPass: Pass {
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<P: Pass + 'static> GraphPass for P {
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+struct Node {
+    pass: Box<dyn GraphPass>,
+    inputs: Vec<Resource>,
+    outputs: Vec<Resource>,
+}
+
+struct Schedule {
+    /// Nodes to draw, in order.
+    order: Vec<usize>,
+    /// Physical target of each transient resource.
+    transients: Vec<Option<TargetRef>>,
+}
+
+/// A set of passes declaring the textures they read and write.
+///
+/// The passes are drawn in the order they were added, so each one reads
+/// what the passes added before it wrote. The graph skips the passes whose
+/// results are never used, and shares the transient targets between passes
+/// that don't need them at the same time. All the passes are submitted together.
+///
+/// Each pass is drawn with `Pass::draw_node`, which by default gives it
+/// its outputs, followed by its inputs, as targets.
+#[derive(Default)]
+pub struct RenderGraph {
+    nodes: Vec<Node>,
+    transients: Vec<TransientDesc>,
+    /// Physical targets allocated so far, shared between transients.
+    pool: Vec<(TransientDesc, TargetRef)>,
+    schedule: Option<Schedule>,
+}
+
+impl RenderGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Declare a transient target of a fixed size.
+    pub fn add_transient(
+        &mut self,
+        format: wgpu::TextureFormat,
+        size: mint::Vector2<u32>,
+    ) -> Resource {
+        self.add_transient_impl(TransientDesc {
+            format,
+            size: TransientSize::Fixed(size),
+        })
+    }
+
+    /// Declare a transient target that is resized together with the surface.
+    pub fn add_screen_transient(&mut self, format: wgpu::TextureFormat) -> Resource {
+        self.add_transient_impl(TransientDesc {
+            format,
+            size: TransientSize::Screen,
+        })
+    }
+
+    fn add_transient_impl(&mut self, desc: TransientDesc) -> Resource {
+        self.schedule = None;
+        self.transients.push(desc);
+        Resource::Transient(self.transients.len() - 1)
+    }
+
+    /// Add a pass reading the `inputs` and drawing into the `outputs`.
+    pub fn add_pass<P: Pass + 'static>(
+        &mut self,
+        pass: P,
+        inputs: &[Resource],
+        outputs: &[Resource],
+    ) -> PassId {
+        self.schedule = None;
+        self.nodes.push(Node {
+            pass: Box::new(pass),
+            inputs: inputs.to_vec(),
+            outputs: outputs.to_vec(),
+        });
+        PassId(self.nodes.len() - 1)
+    }
+
+    pub fn get_pass_mut<P: Pass + 'static>(&mut self, id: PassId) -> Option<&mut P> {
+        self.nodes[id.0].pass.as_any_mut().downcast_mut()
+    }
+
+    /// Schedule the passes and allocate the transient targets.
+    ///
+    /// Has to be called after the graph is changed, before drawing it.
+    pub fn compile(&mut self, context: &mut Context) {
+        let order = self.schedule_passes();
+        let transients = self.assign_transients(&order, |desc| match desc.size {
+            TransientSize::Fixed(size) => context.add_target(desc.format, size),
+            TransientSize::Screen => context.add_screen_target(desc.format),
+        });
+        self.schedule = Some(Schedule { order, transients });
+    }
+
+    /// Assign a physical target to each transient used by the ordered passes,
+    /// reusing the ones no longer needed, and allocating more if needed.
+    fn assign_transients(
+        &mut self,
+        order: &[usize],
+        mut allocate: impl FnMut(TransientDesc) -> TargetRef,
+    ) -> Vec<Option<TargetRef>> {
+        // find the lifetime of each transient, in the order of execution
+        let mut lifetimes = vec![None::<(usize, usize)>; self.transients.len()];
+        for (step, &index) in order.iter().enumerate() {
+            let node = &self.nodes[index];
+            for resource in node.inputs.iter().chain(node.outputs.iter()) {
+                if let Resource::Transient(t) = *resource {
+                    let lifetime = lifetimes[t].get_or_insert((step, step));
+                    lifetime.1 = step;
+                }
+            }
+        }
+
+        let mut transients = vec![None; self.transients.len()];
+        let mut busy_until = vec![None::<usize>; self.pool.len()];
+        let mut by_start = (0..self.transients.len())
+            .filter_map(|t| lifetimes[t].map(|lifetime| (lifetime, t)))
+            .collect::<Vec<_>>();
+        by_start.sort();
+        for ((start, end), t) in by_start {
+            let desc = self.transients[t];
+            let free =
+                self.pool
+                    .iter()
+                    .zip(busy_until.iter())
+                    .position(|(&(pool_desc, _), busy)| match *busy {
+                        Some(until) => pool_desc == desc && until < start,
+                        None => pool_desc == desc,
+                    });
+            let slot = match free {
+                Some(slot) => slot,
+                None => {
+                    self.pool.push((desc, allocate(desc)));
+                    busy_until.push(None);
+                    self.pool.len() - 1
+                }
+            };
+            busy_until[slot] = Some(end);
+            transients[t] = Some(self.pool[slot].1);
+        }
+        transients
+    }
+
+    /// Pick the passes to draw, in the order they were added, so that
+    /// each one reads the contents written by the passes added before it.
+    /// Passes writing only transients that are never read are skipped.
+    fn schedule_passes(&self) -> Vec<usize> {
+        let count = self.nodes.len();
+        // passes whose results each pass uses: the last writer added before it
+        // of every resource it reads or draws on top of
+        let mut sources = vec![Vec::new(); count];
+        for (index, node) in self.nodes.iter().enumerate() {
+            for resource in node.inputs.iter().chain(node.outputs.iter()) {
+                let last_writer =
+                    (0..index).rfind(|&other| self.nodes[other].outputs.contains(resource));
+                if let Some(other) = last_writer {
+                    sources[index].push(other);
+                }
+            }
+        }
+
+        // only keep the passes contributing to non-transient resources
+        let mut needed = vec![false; count];
+        let mut stack = (0..count)
+            .filter(|&index| {
+                self.nodes[index]
+                    .outputs
+                    .iter()
+                    .any(|output| !matches!(*output, Resource::Transient(_)))
+            })
+            .collect::<Vec<_>>();
+        while let Some(index) = stack.pop() {
+            if !needed[index] {
+                needed[index] = true;
+                stack.extend_from_slice(&sources[index]);
+            }
+        }
+        (0..count).filter(|&index| needed[index]).collect()
+    }
+}
+
+impl Pass for RenderGraph {
+    fn draw(&mut self, targets: &[TargetRef], scene: &Scene, camera: &Camera, context: &Context) {
+        let schedule = self
+            .schedule
+            .as_ref()
+            .expect("Render graph is not compiled!");
+        let resolve = |resource: &Resource| match *resource {
+            Resource::Target(index) => targets[index],
+            Resource::External(tr) => tr,
+            Resource::Transient(t) => schedule.transients[t].unwrap(),
+        };
+
+        let batch = context.begin_batch();
+        let (mut outputs, mut inputs) = (Vec::new(), Vec::new());
+        for &index in schedule.order.iter() {
+            let node = &mut self.nodes[index];
+            outputs.clear();
+            outputs.extend(node.outputs.iter().map(resolve));
+            inputs.clear();
+            inputs.extend(node.inputs.iter().map(resolve));
+            node.pass
+                .draw_node(&outputs, &inputs, scene, camera, context);
+        }
+        if batch {
+            context.end_batch();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RenderGraph, Resource, TransientDesc, TransientSize};
+    use crate::{Camera, Context, Pass, Scene, TargetRef};
+
+    struct Dummy;
+
+    impl Pass for Dummy {
+        fn draw(&mut self, _: &[TargetRef], _: &Scene, _: &Camera, _: &Context) {}
+    }
+
+    const T0: Resource = Resource::Transient(0);
+    const OUT: Resource = Resource::Target(0);
+
+    fn graph_with_transients(count: usize) -> RenderGraph {
+        let mut graph = RenderGraph::new();
+        for _ in 0..count {
+            graph.add_screen_transient(wgpu::TextureFormat::Rgba8Unorm);
+        }
+        graph
+    }
+
+    #[test]
+    fn reader_ignores_later_writer() {
+        let mut graph = graph_with_transients(1);
+        graph.add_pass(Dummy, &[], &[T0]);
+        graph.add_pass(Dummy, &[T0], &[OUT]);
+        graph.add_pass(Dummy, &[], &[T0]);
+        assert_eq!(graph.schedule_passes(), vec![0, 1]);
+    }
+
+    #[test]
+    fn read_modify_write_chain() {
+        let mut graph = RenderGraph::new();
+        graph.add_pass(Dummy, &[], &[OUT]);
+        graph.add_pass(Dummy, &[OUT], &[OUT]);
+        graph.add_pass(Dummy, &[OUT], &[OUT]);
+        assert_eq!(graph.schedule_passes(), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn writer_waits_for_earlier_readers() {
+        let mut graph = graph_with_transients(1);
+        graph.add_pass(Dummy, &[], &[T0]);
+        graph.add_pass(Dummy, &[T0], &[Resource::Target(1)]);
+        graph.add_pass(Dummy, &[], &[T0]);
+        graph.add_pass(Dummy, &[T0], &[OUT]);
+        assert_eq!(graph.schedule_passes(), vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn unused_passes_are_skipped() {
+        let mut graph = graph_with_transients(2);
+        graph.add_pass(Dummy, &[], &[T0]);
+        graph.add_pass(Dummy, &[], &[Resource::Transient(1)]);
+        graph.add_pass(Dummy, &[T0], &[OUT]);
+        assert_eq!(graph.schedule_passes(), vec![0, 2]);
+    }
+
+    fn allocate(count: &mut u8) -> impl FnMut(TransientDesc) -> TargetRef + '_ {
+        move |_| {
+            *count += 1;
+            TargetRef(*count)
+        }
+    }
+
+    #[test]
+    fn transients_alias() {
+        let mut graph = graph_with_transients(3);
+        let [t0, t1, t2] = [0, 1, 2].map(Resource::Transient);
+        graph.add_pass(Dummy, &[], &[t0]);
+        graph.add_pass(Dummy, &[t0], &[t1]);
+        graph.add_pass(Dummy, &[t1], &[t2]);
+        graph.add_pass(Dummy, &[t2], &[OUT]);
+        let order = graph.schedule_passes();
+
+        let mut count = 0;
+        let transients = graph.assign_transients(&order, allocate(&mut count));
+        assert_eq!(count, 2);
+        assert_eq!(transients[0], transients[2]);
+        assert_ne!(transients[0], transients[1]);
+
+        // compiling again reuses the same targets
+        let mut count = 0;
+        let again = graph.assign_transients(&order, allocate(&mut count));
+        assert_eq!(count, 0);
+        assert_eq!(again, transients);
+    }
+
+    #[test]
+    fn transients_of_other_formats_dont_alias() {
+        let mut graph = graph_with_transients(1);
+        let t1 = graph.add_transient_impl(TransientDesc {
+            format: wgpu::TextureFormat::Rgba16Float,
+            size: TransientSize::Screen,
+        });
+        graph.add_pass(Dummy, &[], &[T0]);
+        graph.add_pass(Dummy, &[T0], &[OUT]);
+        graph.add_pass(Dummy, &[OUT], &[t1]);
+        graph.add_pass(Dummy, &[t1], &[OUT]);
+        let order = graph.schedule_passes();
+
+        let mut count = 0;
+        let transients = graph.assign_transients(&order, allocate(&mut count));
+        assert_eq!(count, 2);
+        assert_ne!(transients[0], transients[1]);
+    }
+}
@@ -23,16 +23,18 @@
 )]
 
 mod color;
+mod graph;
 mod load;
 mod mesh;
 mod space;
 
 use raw_window_handle::HasRawWindowHandle;
-use std::{mem, ops};
+use std::{mem, ops, sync::Mutex};
 
 pub use color::Color;
+pub use graph::{PassId, RenderGraph, Resource};
 pub use mesh::{IndexStream, Mesh, MeshBuilder, Prototype, Vertex, VertexStream};
-pub use space::{Camera, Projection, RawSpace};
+pub use space::{Camera, Eye, Projection, RawSpace, Stereo, Viewport, MAX_CLIP_PLANES};
 
 pub trait HasWindow: HasRawWindowHandle {
     fn size(&self) -> mint::Vector2<u32>;
@@ -41,18 +43,63 @@ pub trait HasWindow: HasRawWindowHandle {
 struct SurfaceContext {
     raw: wgpu::Surface,
     config: wgpu::SurfaceConfiguration,
+    /// Multisampled color texture, resolved into the frame.
+    msaa_texture: Option<wgpu::Texture>,
+    depth_texture: Option<wgpu::Texture>,
+}
+
+/// Depth buffer attached to a target, shared by the passes drawing into it.
+pub struct TargetDepth {
+    pub view: wgpu::TextureView,
+    pub format: wgpu::TextureFormat,
 }
 
 pub struct Target {
+    /// View to render into. It's multisampled if `sample_count > 1`.
     pub view: wgpu::TextureView,
+    /// Single-sampled view that `view` is resolved into, if it's multisampled.
+    pub resolve_view: Option<wgpu::TextureView>,
     pub format: wgpu::TextureFormat,
     pub size: wgpu::Extent3d,
+    pub sample_count: u32,
+    pub depth: Option<TargetDepth>,
+    /// Single-sampled texture, for offscreen targets.
+    texture: Option<wgpu::Texture>,
 }
 
 impl Target {
     pub fn aspect(&self) -> f32 {
         self.size.width as f32 / self.size.height as f32
     }
+
+    /// Single-sampled view with the final contents, suitable for sampling.
+    pub fn resolved_view(&self) -> &wgpu::TextureView {
+        self.resolve_view.as_ref().unwrap_or(&self.view)
+    }
+
+    /// Single-sampled texture with the final contents, e.g. to copy them
+    /// for reading back. It's only available for offscreen targets.
+    pub fn texture(&self) -> Option<&wgpu::Texture> {
+        self.texture.as_ref()
+    }
+}
+
+fn create_attachment(
+    device: &wgpu::Device,
+    label: &str,
+    format: wgpu::TextureFormat,
+    size: wgpu::Extent3d,
+    sample_count: u32,
+) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some(label),
+        size,
+        mip_level_count: 1,
+        sample_count,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+    })
 }
 
 /// Parameters of a texture target that affect its pipeline compatibility.
@@ -61,14 +108,66 @@ pub struct TargetInfo {
     pub format: wgpu::TextureFormat,
     pub sample_count: u32,
     pub aspect_ratio: f32,
+    /// Format of the attached depth buffer, if any.
+    pub depth_format: Option<wgpu::TextureFormat>,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+/// What a pass does with the existing contents of an attachment.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Load {
+    /// Start from the camera background color, or the far depth.
+    Clear,
+    /// Draw over the contents left by the previous passes.
+    Keep,
+}
+
+/// Load operations of a pass, for its color and depth attachments.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct LoadOps {
+    pub color: Load,
+    pub depth: Load,
+}
+
+impl Default for LoadOps {
+    fn default() -> Self {
+        Self {
+            color: Load::Clear,
+            depth: Load::Clear,
+        }
+    }
+}
+
+impl LoadOps {
+    /// Keep both the color and the depth, e.g. to draw an overlay.
+    pub const KEEP: Self = Self {
+        color: Load::Keep,
+        depth: Load::Keep,
+    };
+
+    pub fn color_op(&self, clear: Color) -> wgpu::LoadOp<wgpu::Color> {
+        match self.color {
+            Load::Clear => wgpu::LoadOp::Clear(clear.into()),
+            Load::Keep => wgpu::LoadOp::Load,
+        }
+    }
+
+    pub fn depth_op(&self, far_depth: f32) -> wgpu::LoadOp<f32> {
+        match self.depth {
+            Load::Clear => wgpu::LoadOp::Clear(far_depth),
+            Load::Keep => wgpu::LoadOp::Load,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub struct TargetRef(u8);
 
 pub struct Image {
     pub view: wgpu::TextureView,
     pub size: wgpu::Extent3d,
+    /// Number of times the view was replaced, e.g. by resizing the target
+    /// the image shows. Passes caching bind groups with the view compare it.
+    pub generation: u32,
 }
 
 pub struct ImageInfo {
@@ -85,14 +184,73 @@ pub struct Context {
     device: wgpu::Device,
     queue: wgpu::Queue,
     targets: Vec<Target>,
+    screen_targets: Vec<TargetRef>,
     images: Vec<Image>,
+    /// Images showing the contents of targets.
+    target_images: Vec<(TargetRef, ImageRef)>,
     meshes: Vec<Mesh>,
+    sample_count: u32,
+    depth_format: Option<wgpu::TextureFormat>,
+    /// Command buffers waiting to be submitted together, if batching.
+    batch: Mutex<Option<Vec<wgpu::CommandBuffer>>>,
 }
 
-#[derive(Default, Debug)]
+#[derive(Debug)]
 pub struct ContextBuilder {
     power_preference: wgpu::PowerPreference,
     software: bool,
+    sample_count: u32,
+    depth_format: Option<wgpu::TextureFormat>,
+}
+
+impl Default for ContextBuilder {
+    fn default() -> Self {
+        Self {
+            power_preference: wgpu::PowerPreference::default(),
+            software: false,
+            sample_count: 1,
+            depth_format: None,
+        }
+    }
+}
+
+impl SurfaceContext {
+    fn extent(&self) -> wgpu::Extent3d {
+        wgpu::Extent3d {
+            width: self.config.width,
+            height: self.config.height,
+            depth_or_array_layers: 1,
+        }
+    }
+
+    fn configure(
+        &mut self,
+        device: &wgpu::Device,
+        sample_count: u32,
+        depth_format: Option<wgpu::TextureFormat>,
+    ) {
+        self.raw.configure(device, &self.config);
+        let size = self.extent();
+        if sample_count > 1 {
+            self.msaa_texture = Some(create_attachment(
+                device,
+                "msaa",
+                self.config.format,
+                size,
+                sample_count,
+            ));
+        }
+        self.depth_texture = depth_format
+            .map(|format| create_attachment(device, "depth", format, size, sample_count));
+    }
+}
+
+/// Device with the optional features used by the passes, if the adapter has them.
+fn device_descriptor(adapter: &wgpu::Adapter) -> wgpu::DeviceDescriptor<'static> {
+    wgpu::DeviceDescriptor {
+        features: adapter.features() & wgpu::Features::MULTIVIEW,
+        ..Default::default()
+    }
 }
 
 impl ContextBuilder {
@@ -111,6 +269,24 @@ impl ContextBuilder {
         Self { software, ..self }
     }
 
+    /// Number of samples per pixel for multisampled anti-aliasing
+    /// of the surface and the targets added to the context.
+    pub fn sample_count(self, sample_count: u32) -> Self {
+        Self {
+            sample_count,
+            ..self
+        }
+    }
+
+    /// Attach a depth buffer to the surface, shared by all the passes
+    /// drawing into a frame.
+    pub fn depth_format(self, format: wgpu::TextureFormat) -> Self {
+        Self {
+            depth_format: Some(format),
+            ..self
+        }
+    }
+
     pub async fn build_offscreen(self) -> Context {
         let instance = wgpu::Instance::new(wgpu::Backends::PRIMARY);
         let adapter = instance
@@ -123,7 +299,7 @@ impl ContextBuilder {
             .unwrap();
 
         let (device, queue) = adapter
-            .request_device(&wgpu::DeviceDescriptor::default(), None)
+            .request_device(&device_descriptor(&adapter), None)
             .await
             .unwrap();
 
@@ -133,8 +309,13 @@ impl ContextBuilder {
             device,
             queue,
             targets: Vec::new(),
+            screen_targets: Vec::new(),
             images: Vec::new(),
+            target_images: Vec::new(),
             meshes: Vec::new(),
+            sample_count: self.sample_count,
+            depth_format: self.depth_format,
+            batch: Mutex::new(None),
         }
     }
 
@@ -152,6 +333,8 @@ impl ContextBuilder {
                 height: size.y,
                 present_mode: wgpu::PresentMode::Mailbox,
             },
+            msaa_texture: None,
+            depth_texture: None,
         };
 
         let adapter = instance
@@ -164,13 +347,13 @@ impl ContextBuilder {
             .unwrap();
 
         let (device, queue) = adapter
-            .request_device(&wgpu::DeviceDescriptor::default(), None)
+            .request_device(&device_descriptor(&adapter), None)
             .await
             .unwrap();
 
         let format = surface.raw.get_preferred_format(&adapter).unwrap();
         surface.config.format = format;
-        surface.raw.configure(&device, &surface.config);
+        surface.configure(&device, self.sample_count, self.depth_format);
 
         Context {
             instance,
@@ -178,8 +361,13 @@ impl ContextBuilder {
             device,
             queue,
             targets: Vec::new(),
+            screen_targets: Vec::new(),
             images: Vec::new(),
+            target_images: Vec::new(),
             meshes: Vec::new(),
+            sample_count: self.sample_count,
+            depth_format: self.depth_format,
+            batch: Mutex::new(None),
         }
     }
 }
@@ -199,30 +387,283 @@ impl Context {
         }
         surface.config.width = width;
         surface.config.height = height;
-        surface.raw.configure(&self.device, &surface.config);
+        surface.configure(&self.device, self.sample_count, self.depth_format);
+
+        for tr in self.screen_targets.clone() {
+            self.resize_target(tr, width, height);
+        }
     }
 
-    pub fn present<P: Pass>(&mut self, pass: &mut P, scene: &Scene, camera: &Camera) {
+    fn create_target(
+        &self,
+        format: wgpu::TextureFormat,
+        size: wgpu::Extent3d,
+        depth_format: Option<wgpu::TextureFormat>,
+    ) -> Target {
+        let texture = self.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("target"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_SRC,
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let (view, resolve_view) = if self.sample_count > 1 {
+            let msaa = create_attachment(&self.device, "msaa", format, size, self.sample_count);
+            (
+                msaa.create_view(&wgpu::TextureViewDescriptor::default()),
+                Some(view),
+            )
+        } else {
+            (view, None)
+        };
+        Target {
+            view,
+            resolve_view,
+            format,
+            size,
+            sample_count: self.sample_count,
+            depth: depth_format.map(|format| self.create_target_depth(format, size)),
+            texture: Some(texture),
+        }
+    }
+
+    fn create_target_depth(
+        &self,
+        format: wgpu::TextureFormat,
+        size: wgpu::Extent3d,
+    ) -> TargetDepth {
+        TargetDepth {
+            view: create_attachment(&self.device, "depth", format, size, self.sample_count)
+                .create_view(&wgpu::TextureViewDescriptor::default()),
+            format,
+        }
+    }
+
+    /// Add an offscreen target, which can be rendered to and sampled from.
+    pub fn add_target(
+        &mut self,
+        format: wgpu::TextureFormat,
+        size: mint::Vector2<u32>,
+    ) -> TargetRef {
+        let target = self.create_target(
+            format,
+            wgpu::Extent3d {
+                width: size.x,
+                height: size.y,
+                depth_or_array_layers: 1,
+            },
+            None,
+        );
+        let index = self.targets.len();
+        self.targets.push(target);
+        TargetRef(index as _)
+    }
+
+    /// Check if the passes supporting stereo can draw both eyes
+    /// into the layers of a target at once, see `add_layered_target`.
+    pub fn supports_multiview(&self) -> bool {
+        self.device.features().contains(wgpu::Features::MULTIVIEW)
+    }
+
+    /// Add an offscreen target with several layers, viewed as a texture array.
+    /// A stereo camera draws an eye into each of its 2 layers, which needs
+    /// the multiview support of the device.
+    pub fn add_layered_target(
+        &mut self,
+        format: wgpu::TextureFormat,
+        size: mint::Vector2<u32>,
+        layers: u32,
+    ) -> TargetRef {
+        assert!(
+            self.supports_multiview(),
+            "Layered targets need the multiview support"
+        );
+        let target = self.create_target(
+            format,
+            wgpu::Extent3d {
+                width: size.x,
+                height: size.y,
+                depth_or_array_layers: layers,
+            },
+            None,
+        );
+        let index = self.targets.len();
+        self.targets.push(target);
+        TargetRef(index as _)
+    }
+
+    /// Add an offscreen target for both eyes of a stereo camera,
+    /// each seen with the given size. It has a layer per eye if the device
+    /// supports multiview, otherwise the eyes are side by side, left eye on the left.
+    pub fn add_stereo_target(
+        &mut self,
+        format: wgpu::TextureFormat,
+        eye_size: mint::Vector2<u32>,
+    ) -> TargetRef {
+        if self.supports_multiview() {
+            self.add_layered_target(format, eye_size, 2)
+        } else {
+            let size = mint::Vector2 {
+                x: 2 * eye_size.x,
+                y: eye_size.y,
+            };
+            self.add_target(format, size)
+        }
+    }
+
+    /// Add an offscreen target that is resized together with the surface.
+    pub fn add_screen_target(&mut self, format: wgpu::TextureFormat) -> TargetRef {
+        let surface = self.surface.as_ref().expect("No screen is configured!");
+        let size = mint::Vector2 {
+            x: surface.config.width,
+            y: surface.config.height,
+        };
+        let tr = self.add_target(format, size);
+        self.screen_targets.push(tr);
+        tr
+    }
+
+    pub fn resize_target(&mut self, tr: TargetRef, width: u32, height: u32) {
+        let target = &self.targets[tr.0 as usize];
+        if (target.size.width, target.size.height) == (width, height) {
+            return;
+        }
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: target.size.depth_or_array_layers,
+        };
+        let depth_format = target.depth.as_ref().map(|depth| depth.format);
+        self.targets[tr.0 as usize] = self.create_target(target.format, size, depth_format);
+
+        for &(target_ref, image_ref) in self.target_images.iter() {
+            if target_ref == tr {
+                let generation = self.images[image_ref.0 as usize].generation + 1;
+                self.images[image_ref.0 as usize] = Image {
+                    generation,
+                    ..self.create_target_image(tr)
+                };
+            }
+        }
+    }
+
+    /// Attach a depth buffer to a target, shared by all the passes drawing into it.
+    pub fn attach_depth(&mut self, tr: TargetRef, format: wgpu::TextureFormat) {
+        let depth = self.create_target_depth(format, self.targets[tr.0 as usize].size);
+        self.targets[tr.0 as usize].depth = Some(depth);
+    }
+
+    fn create_target_image(&self, tr: TargetRef) -> Image {
+        let target = &self.targets[tr.0 as usize];
+        let texture = target
+            .texture
+            .as_ref()
+            .expect("Only offscreen targets can be sampled");
+        assert_eq!(
+            target.size.depth_or_array_layers, 1,
+            "Layered targets can't be sampled as images"
+        );
+        Image {
+            view: texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            size: target.size,
+            generation: 0,
+        }
+    }
+
+    /// Add an image showing the contents of an offscreen target,
+    /// e.g. to use it in a material or a sprite.
+    ///
+    /// The image has the contents of the last pass drawn into the target,
+    /// so the target has to be drawn before the passes that sample it.
+    /// A pass can't sample the target it draws into, so the objects showing
+    /// the image need to be hidden from its camera, e.g. with `Visibility` layers.
+    ///
+    /// Resizing the target replaces the image contents, and the passes
+    /// sampling the image follow it.
+    pub fn add_image_from_target(&mut self, tr: TargetRef) -> ImageRef {
+        let image = self.create_target_image(tr);
+        let image_ref = ImageRef(self.images.len() as u32);
+        self.images.push(image);
+        self.target_images.push((tr, image_ref));
+        image_ref
+    }
+
+    pub fn get_target_info(&self, tr: TargetRef) -> TargetInfo {
+        let target = &self.targets[tr.0 as usize];
+        TargetInfo {
+            format: target.format,
+            sample_count: target.sample_count,
+            aspect_ratio: target.aspect(),
+            depth_format: target.depth.as_ref().map(|depth| depth.format),
+        }
+    }
+
+    /// Start a frame on the surface, in which several passes can be drawn.
+    pub fn begin_frame(&mut self) -> Frame<'_> {
         let surface = self.surface.as_mut().expect("No screen is configured!");
-        let frame = surface.raw.get_current_texture().unwrap();
-        let view = frame
+        let texture = surface.raw.get_current_texture().unwrap();
+        let view = texture
             .texture
             .create_view(&wgpu::TextureViewDescriptor::default());
 
+        let (view, resolve_view) = match surface.msaa_texture {
+            Some(ref msaa) => (
+                msaa.create_view(&wgpu::TextureViewDescriptor::default()),
+                Some(view),
+            ),
+            None => (view, None),
+        };
+
         let tr = TargetRef(self.targets.len() as _);
         self.targets.push(Target {
             view,
+            resolve_view,
             format: surface.config.format,
-            size: wgpu::Extent3d {
-                width: surface.config.width,
-                height: surface.config.height,
-                depth_or_array_layers: 1,
-            },
+            size: surface.extent(),
+            sample_count: self.sample_count,
+            depth: self.depth_format.map(|format| TargetDepth {
+                view: surface
+                    .depth_texture
+                    .as_ref()
+                    .unwrap()
+                    .create_view(&wgpu::TextureViewDescriptor::default()),
+                format,
+            }),
+            texture: None,
         });
 
-        pass.draw(&[tr], scene, camera, self);
+        Frame {
+            context: self,
+            target: tr,
+            texture: Some(texture),
+        }
+    }
+
+    /// Start collecting the submitted command buffers.
+    /// Returns false if they are already collected.
+    fn begin_batch(&self) -> bool {
+        let mut batch = self.batch.lock().unwrap();
+        if batch.is_some() {
+            return false;
+        }
+        *batch = Some(Vec::new());
+        true
+    }
+
+    /// Submit all the collected command buffers at once.
+    fn end_batch(&self) {
+        let command_buffers = self.batch.lock().unwrap().take().unwrap();
+        self.queue.submit(command_buffers);
+    }
 
-        self.targets.pop();
+    pub fn present<P: Pass>(&mut self, pass: &mut P, scene: &Scene, camera: &Camera) {
+        let mut frame = self.begin_frame();
+        frame.draw(pass, scene, camera);
         frame.present();
     }
 
@@ -233,8 +674,9 @@ impl Context {
     pub fn surface_info(&self) -> Option<TargetInfo> {
         self.surface.as_ref().map(|s| TargetInfo {
             format: s.config.format,
-            sample_count: 1,
+            sample_count: self.sample_count,
             aspect_ratio: s.config.width as f32 / s.config.height as f32,
+            depth_format: self.depth_format,
         })
     }
 
@@ -252,6 +694,42 @@ impl Drop for Context {
     }
 }
 
+/// A frame of the surface being drawn.
+///
+/// Dropping it without presenting discards the frame.
+pub struct Frame<'a> {
+    context: &'a mut Context,
+    target: TargetRef,
+    texture: Option<wgpu::SurfaceTexture>,
+}
+
+impl Frame<'_> {
+    /// Target of the surface for this frame.
+    pub fn target(&self) -> TargetRef {
+        self.target
+    }
+
+    pub fn context(&self) -> &Context {
+        self.context
+    }
+
+    /// Draw a pass into the frame, after the passes drawn before.
+    pub fn draw<P: Pass>(&mut self, pass: &mut P, scene: &Scene, camera: &Camera) -> &mut Self {
+        pass.draw(&[self.target], scene, camera, self.context);
+        self
+    }
+
+    pub fn present(mut self) {
+        self.texture.take().unwrap().present();
+    }
+}
+
+impl Drop for Frame<'_> {
+    fn drop(&mut self) {
+        self.context.targets.pop();
+    }
+}
+
 /// Trait that exposes `Context` details that depend on `wgpu`
 pub trait ContextDetail {
     fn get_target(&self, tr: TargetRef) -> &Target;
@@ -259,6 +737,8 @@ pub trait ContextDetail {
     fn get_image(&self, ir: ImageRef) -> &Image;
     fn device(&self) -> &wgpu::Device;
     fn queue(&self) -> &wgpu::Queue;
+    /// Submit the work of a pass, possibly batched with other passes.
+    fn submit(&self, command_buffer: wgpu::CommandBuffer);
 }
 
 impl ContextDetail for Context {
@@ -277,12 +757,78 @@ impl ContextDetail for Context {
     fn queue(&self) -> &wgpu::Queue {
         &self.queue
     }
+    fn submit(&self, command_buffer: wgpu::CommandBuffer) {
+        match *self.batch.lock().unwrap() {
+            Some(ref mut batch) => batch.push(command_buffer),
+            None => {
+                self.queue.submit(Some(command_buffer));
+            }
+        }
+    }
 }
 
+/// Something that draws a scene into a list of targets.
+///
+/// The first target receives the main color output. Passes producing
+/// more data, such as normals, write it into the following targets.
+/// Passes reading other targets, such as post-processing,
+/// expect them after their outputs.
+///
+/// Most passes are created for a single `TargetInfo`, describing
+/// the first target, and create more pipelines when drawn into other kinds
+/// of targets. Passes writing several outputs, such as `Real` in baryon,
+/// take the infos of all of them. Those are drawn in one render pass,
+/// so they need the same sample count.
 pub trait Pass {
     fn draw(&mut self, targets: &[TargetRef], scene: &Scene, camera: &Camera, context: &Context);
+
+    /// Draw as a node of a `RenderGraph`, given the targets it draws into
+    /// and the ones it reads. By default the inputs follow the outputs.
+    fn draw_node(
+        &mut self,
+        outputs: &[TargetRef],
+        inputs: &[TargetRef],
+        scene: &Scene,
+        camera: &Camera,
+        context: &Context,
+    ) {
+        let targets = outputs.iter().chain(inputs).copied().collect::<Vec<_>>();
+        self.draw(&targets, scene, camera, context);
+    }
 }
 
+/// Pass adapter that draws into its own targets,
+/// ignoring the ones it's given.
+pub struct Offscreen<P> {
+    pub pass: P,
+    pub targets: Vec<TargetRef>,
+}
+
+impl<P: Pass> Pass for Offscreen<P> {
+    fn draw(&mut self, _targets: &[TargetRef], scene: &Scene, camera: &Camera, context: &Context) {
+        self.pass.draw(&self.targets, scene, camera, context);
+    }
+}
+
+macro_rules! impl_pass_tuple {
+    ($($name:ident),*) => {
+        /// Passes in a tuple are drawn in order, into the same targets.
+        impl<$($name: Pass),*> Pass for ($($name,)*) {
+            #[allow(non_snake_case)]
+            fn draw(&mut self, targets: &[TargetRef], scene: &Scene, camera: &Camera, context: &Context) {
+                let ($(ref mut $name,)*) = *self;
+                $(
+                    $name.draw(targets, scene, camera, context);
+                )*
+            }
+        }
+    };
+}
+
+impl_pass_tuple!(A, B);
+impl_pass_tuple!(A, B, C);
+impl_pass_tuple!(A, B, C, D);
+
 #[derive(Clone, Copy, Debug, Default, PartialEq)]
 pub struct NodeRef(u32);
 
@@ -474,6 +1020,39 @@ pub struct Sprite {
     pub uv: Option<UvRange>,
 }
 
+/// Visibility of an entity or a sprite, attached as a component.
+///
+/// Objects without it are visible, and belong to `Visibility::DEFAULT_LAYER`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Visibility {
+    /// Hide the object from all cameras.
+    pub hidden: bool,
+    /// Mask of the layers the object belongs to. It's only drawn
+    /// by the cameras that have any of these layers in their mask.
+    pub layers: u32,
+}
+
+impl Default for Visibility {
+    fn default() -> Self {
+        Self {
+            hidden: false,
+            layers: Self::DEFAULT_LAYER,
+        }
+    }
+}
+
+impl Visibility {
+    pub const DEFAULT_LAYER: u32 = 1;
+
+    /// Visible object in the given layers.
+    pub fn layers(layers: u32) -> Self {
+        Self {
+            hidden: false,
+            layers,
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug)]
 pub enum LightKind {
     Directional,
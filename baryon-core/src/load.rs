@@ -9,7 +9,30 @@ impl super::Context {
     ) -> super::ImageRef {
         let index = self.images.len();
         let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
-        self.images.push(super::Image { view, size });
+        self.images.push(super::Image {
+            view,
+            size,
+            generation: 0,
+        });
+        super::ImageRef(index as u32)
+    }
+
+    /// Add a cube map image, with 6 array layers ordered as +X, -X, +Y, -Y, +Z, -Z.
+    pub fn add_cube_image_from_raw(
+        &mut self,
+        texture: wgpu::Texture,
+        size: wgpu::Extent3d,
+    ) -> super::ImageRef {
+        let index = self.images.len();
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::Cube),
+            ..Default::default()
+        });
+        self.images.push(super::Image {
+            view,
+            size,
+            generation: 0,
+        });
         super::ImageRef(index as u32)
     }
 
@@ -24,6 +47,10 @@ impl super::Context {
         self.add_image_from_raw(texture, desc.size)
     }
 
+    /// Load an image from a file.
+    ///
+    /// DDS cube maps produce cube images. HDR images keep their dynamic range,
+    /// and are typically used as equirectangular panoramas.
     pub fn load_image(&mut self, path_ref: impl AsRef<Path>) -> super::ImageRef {
         let path = path_ref.as_ref();
         let image_format = image::ImageFormat::from_extension(path.extension().unwrap())
@@ -34,14 +61,29 @@ impl super::Context {
             .unwrap_or_else(|e| panic!("Unable to open {}: {:?}", path.display(), e));
         let mut buf_reader = io::BufReader::new(file);
 
+        let mut is_cube = false;
         let (texture, size) = if image_format == image::ImageFormat::Dds {
             let dds = ddsfile::Dds::read(&mut buf_reader)
                 .unwrap_or_else(|e| panic!("Unable to read {}: {:?}", path.display(), e));
 
             println!("Header {:?}", dds.header);
             let mip_level_count = dds.get_num_mipmap_levels();
+            is_cube = match dds.header10 {
+                Some(ref h) => h.misc_flag.contains(ddsfile::MiscFlag::TEXTURECUBE),
+                None => dds.header.caps2.contains(ddsfile::Caps2::CUBEMAP),
+            };
             let (dimension, depth_or_array_layers) = match dds.header10 {
                 Some(ref h) => match h.resource_dimension {
+                    ddsfile::D3D10ResourceDimension::Texture2D if is_cube => {
+                        // cube images are viewed as a single cube
+                        assert_eq!(
+                            h.array_size,
+                            1,
+                            "Cube map arrays are not supported: {}",
+                            path.display()
+                        );
+                        (wgpu::TextureDimension::D2, 6)
+                    }
                     ddsfile::D3D10ResourceDimension::Texture2D => {
                         (wgpu::TextureDimension::D2, h.array_size)
                     }
@@ -50,6 +92,7 @@ impl super::Context {
                     }
                     other => panic!("Unsupported resource dimension {:?}", other),
                 },
+                None if is_cube => (wgpu::TextureDimension::D2, 6),
                 None => match dds.header.depth {
                     None | Some(1) => (wgpu::TextureDimension::D2, 1),
                     Some(other) => (wgpu::TextureDimension::D3, other),
@@ -89,6 +132,39 @@ impl super::Context {
                 .device
                 .create_texture_with_data(&self.queue, &desc, &dds.data);
 
+            (texture, desc.size)
+        } else if image_format == image::ImageFormat::Hdr {
+            let decoder = image::codecs::hdr::HdrDecoder::new(buf_reader)
+                .unwrap_or_else(|e| panic!("Unable to decode {}: {:?}", path.display(), e));
+            let meta = decoder.metadata();
+            let pixels = decoder
+                .read_image_hdr()
+                .unwrap_or_else(|e| panic!("Unable to decode {}: {:?}", path.display(), e));
+            // keep the dynamic range, but pack into a filterable format
+            let data = pixels
+                .iter()
+                .flat_map(|p| [p.0[0], p.0[1], p.0[2], 1.0])
+                .map(|v| half::f16::from_f32(v).to_bits())
+                .collect::<Vec<u16>>();
+
+            let desc = wgpu::TextureDescriptor {
+                label: Some(&label),
+                size: wgpu::Extent3d {
+                    width: meta.width,
+                    height: meta.height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba16Float,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING,
+            };
+            let texture = self.device.create_texture_with_data(
+                &self.queue,
+                &desc,
+                bytemuck::cast_slice(&data),
+            );
             (texture, desc.size)
         } else {
             let img = image::load(buf_reader, image_format)
@@ -125,6 +201,74 @@ impl super::Context {
             (texture, size)
         };
 
-        self.add_image_from_raw(texture, size)
+        if is_cube {
+            self.add_cube_image_from_raw(texture, size)
+        } else {
+            self.add_image_from_raw(texture, size)
+        }
+    }
+
+    /// Load a cube map from 6 separate images of the same square size.
+    ///
+    /// The faces are ordered as +X, -X, +Y, -Y, +Z, -Z.
+    pub fn load_cube_image(&mut self, face_paths: [impl AsRef<Path>; 6]) -> super::ImageRef {
+        let mut faces = Vec::with_capacity(6);
+        for path_ref in face_paths.iter() {
+            let path = path_ref.as_ref();
+            let image_format = image::ImageFormat::from_extension(path.extension().unwrap())
+                .unwrap_or_else(|| panic!("Unrecognized image extension: {:?}", path.extension()));
+            let file = File::open(path)
+                .unwrap_or_else(|e| panic!("Unable to open {}: {:?}", path.display(), e));
+            let img = image::load(io::BufReader::new(file), image_format)
+                .unwrap_or_else(|e| panic!("Unable to decode {}: {:?}", path.display(), e))
+                .to_rgba8();
+            faces.push(img);
+        }
+
+        let (width, height) = faces[0].dimensions();
+        assert_eq!(width, height, "Cube faces have to be square");
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 6,
+        };
+        let texture = self.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("cube"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::TEXTURE_BINDING,
+        });
+
+        for (layer, img) in faces.iter().enumerate() {
+            assert_eq!(img.dimensions(), (width, height), "Cube face size mismatch");
+            self.queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture: &texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d {
+                        x: 0,
+                        y: 0,
+                        z: layer as u32,
+                    },
+                    aspect: wgpu::TextureAspect::All,
+                },
+                img,
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: std::num::NonZeroU32::new(width * 4),
+                    rows_per_image: None,
+                },
+                wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+
+        self.add_cube_image_from_raw(texture, size)
     }
 }
@@ -125,6 +125,12 @@ impl super::Node {
         self.local.orientation = self.local.orientation
             * glam::Quat::from_axis_angle(axis.into(), angle_deg.to_radians());
     }
+    pub fn get_orientation(&self) -> mint::Quaternion<f32> {
+        self.local.orientation.into()
+    }
+    pub fn set_orientation(&mut self, quat: mint::Quaternion<f32>) {
+        self.local.orientation = quat.into();
+    }
     pub fn post_rotate(&mut self, axis: mint::Vector3<f32>, angle_deg: f32) {
         let other = Space {
             position: glam::Vec3::ZERO,
@@ -185,16 +191,145 @@ pub enum Projection {
         /// Note: the horizontal FOV is computed based on the aspect.
         fov_y: f32,
     },
+    /// Orthographic projection with explicit bounds, ignoring the aspect.
+    OrthographicBounds {
+        left: f32,
+        right: f32,
+        bottom: f32,
+        top: f32,
+    },
+    /// Perspective projection with an asymmetric frustum, ignoring the aspect.
+    /// The bounds are the tangents of the angles between the view direction
+    /// and the sides of the frustum, i.e. the bounds at a distance of 1.
+    /// This fits tiled displays, and screens following the head of the viewer.
+    Frustum {
+        left: f32,
+        right: f32,
+        bottom: f32,
+        top: f32,
+    },
+    /// Projection matrix used as is, ignoring the aspect and the depth range.
+    /// The depth range still tells if the depth is reversed.
+    Custom(mint::ColumnMatrix4<f32>),
+}
+
+/// Rectangle of a target in normalized coordinates,
+/// with (0, 0) at the top left corner and (1, 1) at the bottom right.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Viewport {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Default for Viewport {
+    fn default() -> Self {
+        Self::FULL
+    }
+}
+
+impl Viewport {
+    pub const FULL: Self = Self {
+        x: 0.0,
+        y: 0.0,
+        width: 1.0,
+        height: 1.0,
+    };
+
+    /// Rectangle in pixels, within a target of the given size.
+    pub fn to_pixels(self, size: wgpu::Extent3d) -> [f32; 4] {
+        [
+            self.x * size.width as f32,
+            self.y * size.height as f32,
+            self.width * size.width as f32,
+            self.height * size.height as f32,
+        ]
+    }
+
+    /// Rectangle in pixels, clamped to a target of the given size.
+    fn to_clamped_pixels(self, size: wgpu::Extent3d) -> [f32; 4] {
+        let [x, y, width, height] = self.to_pixels(size);
+        let (max_x, max_y) = (size.width as f32, size.height as f32);
+        let x0 = x.clamp(0.0, max_x);
+        let y0 = y.clamp(0.0, max_y);
+        let x1 = (x + width).clamp(0.0, max_x);
+        let y1 = (y + height).clamp(0.0, max_y);
+        [x0, y0, (x1 - x0).max(0.0), (y1 - y0).max(0.0)]
+    }
+
+    /// Rectangle in whole pixels, clamped to a target of the given size.
+    fn to_scissor(self, size: wgpu::Extent3d) -> [u32; 4] {
+        let [x, y, width, height] = self.to_pixels(size);
+        let x0 = (x.round().max(0.0) as u32).min(size.width);
+        let y0 = (y.round().max(0.0) as u32).min(size.height);
+        let x1 = ((x + width).round().max(0.0) as u32).min(size.width);
+        let y1 = ((y + height).round().max(0.0) as u32).min(size.height);
+        [x0, y0, x1.max(x0) - x0, y1.max(y0) - y0]
+    }
 }
 
+/// Parameters of a stereo camera, see `Camera::eye`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Stereo {
+    /// Distance between the eyes, along the X axis of the camera node.
+    pub eye_separation: f32,
+    /// Distance to the plane where both eyes see the same picture.
+    /// Objects in front of it appear to come out of the screen.
+    pub convergence: f32,
+}
+
+impl Default for Stereo {
+    fn default() -> Self {
+        Self {
+            eye_separation: 0.064,
+            convergence: 2.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Eye {
+    Left,
+    Right,
+}
+
+impl Eye {
+    pub const BOTH: [Self; 2] = [Self::Left, Self::Right];
+}
+
+/// Maximum number of clip planes of a camera.
+pub const MAX_CLIP_PLANES: usize = 4;
+
 #[derive(Clone, Debug)]
 pub struct Camera {
     pub projection: Projection,
     /// Specify the depth range as seen by the camera.
     /// `depth.start` maps to 0.0, and `depth.end` maps to 1.0.
+    /// A reversed range, such as `f32::INFINITY..0.1`, gives a better
+    /// precision in the distance, see `reverse_depth`.
     pub depth: ops::Range<f32>,
     pub node: super::NodeRef,
     pub background: super::Color,
+    /// Part of the target to draw into, the whole target if `None`.
+    /// It may reach past the target, with the parts outside cut off.
+    /// Clearing still affects the whole target, so views sharing a target
+    /// should keep the contents drawn by the previous ones with `LoadOps`.
+    pub viewport: Option<Viewport>,
+    /// Part of the target outside of which nothing is drawn.
+    pub scissor: Option<Viewport>,
+    /// Mask of the layers this camera sees, see `Visibility`.
+    pub layers: u32,
+    /// Planes in world space, given as the normal and the distance.
+    /// Everything on the negative side of any of them is cut away.
+    pub clip_planes: Vec<mint::Vector4<f32>>,
+    /// Color of the section caps covering the insides of the cut meshes.
+    /// This only works for closed meshes, and needs a stencil aspect
+    /// in the depth buffer attached to the target, if any.
+    pub clip_cap: Option<super::Color>,
+    /// Stereo parameters, for the passes drawing a view per eye, see `views`.
+    /// Passes drawing a single view of the scene panic on stereo cameras.
+    pub stereo: Option<Stereo>,
 }
 
 impl Default for Camera {
@@ -207,11 +342,117 @@ impl Default for Camera {
             depth: 0.0..1.0,
             node: super::NodeRef::default(),
             background: super::Color::default(),
+            viewport: None,
+            scissor: None,
+            layers: !0,
+            clip_planes: Vec::new(),
+            clip_cap: None,
+            stereo: None,
         }
     }
 }
 
 impl Camera {
+    /// Check if an object with the given visibility component is seen by the camera.
+    pub fn sees(&self, visibility: Option<&super::Visibility>) -> bool {
+        match visibility {
+            Some(vis) => !vis.hidden && vis.layers & self.layers != 0,
+            None => super::Visibility::DEFAULT_LAYER & self.layers != 0,
+        }
+    }
+
+    /// Aspect ratio of the viewport within a target of the given size.
+    pub fn aspect(&self, size: wgpu::Extent3d) -> f32 {
+        let [_, _, width, height] = self.viewport.unwrap_or_default().to_pixels(size);
+        width / height
+    }
+
+    /// Restrict a render pass into a target of the given size
+    /// to the viewport and the scissor of the camera.
+    /// The viewport is clamped to the target, see `target_projection_matrix`.
+    pub fn apply_viewport(&self, pass: &mut wgpu::RenderPass, size: wgpu::Extent3d) {
+        if let Some(viewport) = self.viewport {
+            let [x, y, width, height] = viewport.to_clamped_pixels(size);
+            assert!(
+                width > 0.0 && height > 0.0,
+                "Viewport {:?} is outside of the target",
+                viewport
+            );
+            pass.set_viewport(x, y, width, height, 0.0, 1.0);
+        }
+        if let Some(scissor) = self.scissor {
+            let [x, y, width, height] = scissor.to_scissor(size);
+            pass.set_scissor_rect(x, y, width, height);
+        }
+    }
+
+    /// Check if the depth is reversed, with the far plane at 0.0.
+    pub fn reverse_depth(&self) -> bool {
+        self.depth.start > self.depth.end
+    }
+
+    /// Depth of the far plane, which the depth buffer is cleared to.
+    pub fn far_depth(&self) -> f32 {
+        if self.reverse_depth() {
+            0.0
+        } else {
+            1.0
+        }
+    }
+
+    /// Depth test keeping the fragments closest to the camera.
+    pub fn depth_compare(&self) -> wgpu::CompareFunction {
+        if self.reverse_depth() {
+            wgpu::CompareFunction::GreaterEqual
+        } else {
+            wgpu::CompareFunction::LessEqual
+        }
+    }
+
+    /// Clip planes as seen by the shaders, the unused ones cutting nothing.
+    pub fn clip_plane_array(&self) -> [[f32; 4]; MAX_CLIP_PLANES] {
+        assert!(
+            self.clip_planes.len() <= MAX_CLIP_PLANES,
+            "Too many clip planes: {}",
+            self.clip_planes.len()
+        );
+        let mut planes = [[0.0, 0.0, 0.0, 1.0]; MAX_CLIP_PLANES];
+        for (plane, clip) in planes.iter_mut().zip(self.clip_planes.iter()) {
+            *plane = (*clip).into();
+        }
+        planes
+    }
+
+    /// Projection for drawing into a target of the given size.
+    /// If the viewport reaches past the target, the projection is cropped
+    /// to the part within it, matching the clamped viewport of `apply_viewport`.
+    pub fn target_projection_matrix(&self, size: wgpu::Extent3d) -> mint::ColumnMatrix4<f32> {
+        let matrix = self.projection_matrix(self.aspect(size));
+        let viewport = match self.viewport {
+            Some(viewport) => viewport,
+            None => return matrix,
+        };
+        let [x, y, width, height] = viewport.to_pixels(size);
+        let [cx, cy, cw, ch] = viewport.to_clamped_pixels(size);
+        if [x, y, width, height] == [cx, cy, cw, ch] || cw <= 0.0 || ch <= 0.0 {
+            return matrix;
+        }
+        // map the clip space of the whole viewport to the clamped one,
+        // with Y going up in the clip space and down in the pixels
+        let crop = glam::Mat4::from_cols(
+            glam::Vec4::new(width / cw, 0.0, 0.0, 0.0),
+            glam::Vec4::new(0.0, height / ch, 0.0, 0.0),
+            glam::Vec4::Z,
+            glam::Vec4::new(
+                (2.0 * (x - cx) + width - cw) / cw,
+                (2.0 * (cy - y) + ch - height) / ch,
+                0.0,
+                1.0,
+            ),
+        );
+        (crop * glam::Mat4::from(matrix)).into()
+    }
+
     pub fn projection_matrix(&self, aspect: f32) -> mint::ColumnMatrix4<f32> {
         let matrix = match self.projection {
             Projection::Orthographic { center, extent_y } => {
@@ -225,18 +466,105 @@ impl Camera {
                     self.depth.end,
                 )
             }
-            Projection::Perspective { fov_y } => {
-                let fov = fov_y.to_radians();
-                if self.depth.end == f32::INFINITY {
-                    assert!(self.depth.start.is_finite());
-                    glam::Mat4::perspective_infinite_rh(fov, aspect, self.depth.start)
-                } else if self.depth.start == f32::INFINITY {
-                    glam::Mat4::perspective_infinite_reverse_rh(fov, aspect, self.depth.end)
-                } else {
-                    glam::Mat4::perspective_rh(fov, aspect, self.depth.start, self.depth.end)
-                }
+            Projection::Perspective { fov_y } => self.perspective(fov_y.to_radians(), aspect),
+            Projection::OrthographicBounds {
+                left,
+                right,
+                bottom,
+                top,
+            } => glam::Mat4::orthographic_rh(
+                left,
+                right,
+                bottom,
+                top,
+                self.depth.start,
+                self.depth.end,
+            ),
+            Projection::Frustum {
+                left,
+                right,
+                bottom,
+                top,
+            } => {
+                // a square 90 degree view has the bounds of -1 to 1,
+                // only the X and Y mapping need to be adjusted
+                let mut matrix = self.perspective(std::f32::consts::FRAC_PI_2, 1.0);
+                matrix.x_axis.x = 2.0 / (right - left);
+                matrix.y_axis.y = 2.0 / (top - bottom);
+                matrix.z_axis.x = (right + left) / (right - left);
+                matrix.z_axis.y = (top + bottom) / (top - bottom);
+                matrix
             }
+            Projection::Custom(matrix) => glam::Mat4::from(matrix),
         };
         matrix.into()
     }
+
+    /// Mono camera seeing the scene from one eye of this stereo camera.
+    /// The eye is offset from the node along its X axis, and the frustum
+    /// is skewed to meet the one of the other eye at the convergence distance.
+    /// The aspect is the one of the target the eye is drawn into.
+    pub fn eye(&self, eye: Eye, aspect: f32) -> Self {
+        let stereo = self.stereo.expect("Camera is not stereo");
+        let offset = match eye {
+            Eye::Left => -0.5,
+            Eye::Right => 0.5,
+        } * stereo.eye_separation;
+        let mut matrix = glam::Mat4::from(self.projection_matrix(aspect));
+        matrix.z_axis.x -= matrix.x_axis.x * offset / stereo.convergence;
+        let matrix = matrix * glam::Mat4::from_translation(glam::Vec3::new(-offset, 0.0, 0.0));
+        Self {
+            projection: Projection::Custom(matrix.into()),
+            stereo: None,
+            ..self.clone()
+        }
+    }
+
+    /// Cameras of the views drawn into a target of the given size by the passes
+    /// supporting stereo: the eyes of a stereo camera, or just this camera.
+    /// A layered target has an eye in each layer, both seen through the viewport
+    /// of this camera, otherwise the viewport is split in halves, left eye on the left.
+    pub fn views(&self, size: wgpu::Extent3d) -> Vec<Self> {
+        let layers = size.depth_or_array_layers;
+        if self.stereo.is_none() {
+            assert_eq!(layers, 1, "Layered targets need a stereo camera");
+            return vec![self.clone()];
+        }
+        assert!(layers <= 2, "Stereo targets need a layer per eye");
+        let viewport = self.viewport.unwrap_or_default();
+        Eye::BOTH
+            .iter()
+            .enumerate()
+            .map(|(i, &eye)| {
+                let viewport = if layers == 2 {
+                    self.viewport
+                } else {
+                    let width = 0.5 * viewport.width;
+                    Some(Viewport {
+                        x: viewport.x + i as f32 * width,
+                        width,
+                        ..viewport
+                    })
+                };
+                let mut camera = self.clone();
+                camera.viewport = viewport;
+                let aspect = camera.aspect(size);
+                Self {
+                    viewport,
+                    ..self.eye(eye, aspect)
+                }
+            })
+            .collect()
+    }
+
+    fn perspective(&self, fov_y: f32, aspect: f32) -> glam::Mat4 {
+        if self.depth.end == f32::INFINITY {
+            assert!(self.depth.start.is_finite());
+            glam::Mat4::perspective_infinite_rh(fov_y, aspect, self.depth.start)
+        } else if self.depth.start == f32::INFINITY {
+            glam::Mat4::perspective_infinite_reverse_rh(fov_y, aspect, self.depth.end)
+        } else {
+            glam::Mat4::perspective_rh(fov_y, aspect, self.depth.start, self.depth.end)
+        }
+    }
 }
@@ -150,6 +150,7 @@ fn main() {
             .look_at([0f32; 3].into(), [0f32, 0.0, 1.0].into())
             .build(),
         background: baryon::Color(0xFF203040),
+        ..Default::default()
     };
 
     let prototype = Geometry::cuboid(
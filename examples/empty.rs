@@ -20,7 +20,7 @@ impl baryon::Pass for Clear {
                 label: Some("clear"),
                 color_attachments: &[wgpu::RenderPassColorAttachment {
                     view: &target.view,
-                    resolve_target: None,
+                    resolve_target: target.resolve_view.as_ref(),
                     ops: wgpu::Operations {
                         load: wgpu::LoadOp::Clear(camera.background.into()),
                         store: true,
@@ -30,7 +30,7 @@ impl baryon::Pass for Clear {
             });
         }
 
-        context.queue().submit(Some(encoder.finish()));
+        context.submit(encoder.finish());
     }
 }
 
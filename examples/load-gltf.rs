@@ -17,13 +17,37 @@ fn main() {
         &mut context,
     );
 
-    let mut pass = baryon::pass::Real::new(
+    let hdr_target = context.add_screen_target(wgpu::TextureFormat::Rgba16Float);
+    let real = baryon::pass::Real::new_offscreen(
         &baryon::pass::RealConfig {
             cull_back_faces: true,
             max_lights: 4,
+            ambient_occlusion: Some(Default::default()),
+            ..Default::default()
         },
+        context.get_target_info(hdr_target),
         &context,
     );
+    let bloom = baryon::pass::Bloom::new_offscreen(
+        &baryon::pass::BloomConfig::default(),
+        context.get_target_info(hdr_target),
+        &context,
+    );
+    let tonemap = baryon::pass::Tonemap::new(
+        &baryon::pass::TonemapConfig {
+            exposure: baryon::pass::Exposure::Auto(Default::default()),
+            ..Default::default()
+        },
+        hdr_target,
+        &context,
+    );
+    let mut pass = (
+        baryon::Offscreen {
+            pass: (real, bloom),
+            targets: vec![hdr_target],
+        },
+        tonemap,
+    );
 
     window.run(move |event| match event {
         Event::Resize { width, height } => {
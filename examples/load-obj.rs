@@ -14,6 +14,7 @@ fn main() {
             .look_at([1.0, 0.0, 0.0].into(), [0f32, 1.0, 0.0].into())
             .build(),
         background: baryon::Color(0xFF203040),
+        ..Default::default()
     };
 
     let _entities = baryon::asset::load_obj(
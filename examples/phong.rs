@@ -5,7 +5,7 @@ fn main() {
     };
 
     let window = Window::new().title("Phong").build();
-    let mut context = pollster::block_on(baryon::Context::init().build(&window));
+    let mut context = pollster::block_on(baryon::Context::init().sample_count(4).build(&window));
     let mut scene = baryon::Scene::new();
 
     let camera = baryon::Camera {
@@ -17,6 +17,7 @@ fn main() {
             .look_at([0f32; 3].into(), [0f32, 0.0, 1.0].into())
             .build(),
         background: baryon::Color(0xFF203040),
+        ..Default::default()
     };
 
     let _point_light = scene
@@ -1,5 +1,6 @@
 fn main() {
     use baryon::{
+        control::{Controller as _, Orbit},
         geometry::{Geometry, Streams},
         pass::{Phong, PhongConfig, Shader},
         window::{Event, Window},
@@ -19,6 +20,7 @@ fn main() {
             .look_at([0f32; 3].into(), [0f32, 1.0, 0.0].into())
             .build(),
         background: Color::BLACK_OPAQUE,
+        ..Default::default()
     };
 
     scene
@@ -39,6 +41,8 @@ fn main() {
         .component(Shader::Phong { glossiness: 100 })
         .build();
 
+    let mut orbit = Orbit::new(&camera, [0f32; 3].into(), [0f32, 1.0, 0.0].into(), &scene);
+
     let mut pass = Phong::new(
         &PhongConfig {
             ..Default::default()
@@ -46,13 +50,16 @@ fn main() {
         &context,
     );
 
-    window.run(move |event| match event {
-        Event::Resize { width, height } => {
-            context.resize(width, height);
-        }
-        Event::Draw => {
-            context.present(&mut pass, &scene, &camera);
+    window.run(move |event| {
+        orbit.update(&event, &mut scene);
+        match event {
+            Event::Resize { width, height } => {
+                context.resize(width, height);
+            }
+            Event::Draw => {
+                context.present(&mut pass, &scene, &camera);
+            }
+            _ => {}
         }
-        _ => {}
     })
 }
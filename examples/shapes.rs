@@ -50,6 +50,7 @@ fn main() {
             .look_at([0f32; 3].into(), [0f32, -1.0, 0.0].into())
             .build(),
         background: baryon::Color::BLACK_OPAQUE,
+        ..Default::default()
     };
 
     let mut pass = pass::Solid::new(
@@ -17,9 +17,10 @@ struct Primitive {
     color: crate::Color,
     shader: crate::pass::Shader,
     material: crate::pass::Material,
+    culling: crate::pass::Culling,
 }
 
-fn load_texture(mut data: gltf::image::Data, context: &mut crate::Context) -> Texture {
+fn load_texture(mut data: gltf::image::Data, srgb: bool, context: &mut crate::Context) -> Texture {
     let format = match data.format {
         gltf::image::Format::R8 => wgpu::TextureFormat::R8Unorm,
         gltf::image::Format::R8G8 => wgpu::TextureFormat::Rg8Unorm,
@@ -37,15 +38,18 @@ fn load_texture(mut data: gltf::image::Data, context: &mut crate::Context) -> Te
                 data.pixels.push(chunk[2]);
                 data.pixels.push(0xFF);
             }
-            if data.format == gltf::image::Format::R8G8B8 {
-                wgpu::TextureFormat::Rgba8UnormSrgb
-            } else {
-                wgpu::TextureFormat::Bgra8UnormSrgb
+            match (data.format == gltf::image::Format::R8G8B8, srgb) {
+                (true, true) => wgpu::TextureFormat::Rgba8UnormSrgb,
+                (true, false) => wgpu::TextureFormat::Rgba8Unorm,
+                (false, true) => wgpu::TextureFormat::Bgra8UnormSrgb,
+                (false, false) => wgpu::TextureFormat::Bgra8Unorm,
             }
         }
         gltf::image::Format::R16G16B16 => panic!("RGB16 is outdated"),
-        gltf::image::Format::R8G8B8A8 => wgpu::TextureFormat::Rgba8UnormSrgb,
-        gltf::image::Format::B8G8R8A8 => wgpu::TextureFormat::Bgra8UnormSrgb,
+        gltf::image::Format::R8G8B8A8 if srgb => wgpu::TextureFormat::Rgba8UnormSrgb,
+        gltf::image::Format::R8G8B8A8 => wgpu::TextureFormat::Rgba8Unorm,
+        gltf::image::Format::B8G8R8A8 if srgb => wgpu::TextureFormat::Bgra8UnormSrgb,
+        gltf::image::Format::B8G8R8A8 => wgpu::TextureFormat::Bgra8Unorm,
         gltf::image::Format::R16 => wgpu::TextureFormat::R16Float,
         gltf::image::Format::R16G16 => wgpu::TextureFormat::Rg16Float,
         gltf::image::Format::R16G16B16A16 => wgpu::TextureFormat::Rgba16Float,
@@ -107,15 +111,30 @@ fn load_primitive<'a>(
     let mat = primitive.material();
     let pbr = mat.pbr_metallic_roughness();
     let base_color = pbr.base_color_factor();
+    let occlusion = mat.occlusion_texture();
     let material = crate::pass::Material {
+        alpha_mode: match mat.alpha_mode() {
+            gltf::material::AlphaMode::Opaque => crate::pass::AlphaMode::Opaque,
+            gltf::material::AlphaMode::Mask => crate::pass::AlphaMode::Mask {
+                cutoff: mat.alpha_cutoff().unwrap_or(0.5),
+            },
+            gltf::material::AlphaMode::Blend => crate::pass::AlphaMode::Blend,
+        },
         base_color_map: pbr
             .base_color_texture()
             .map(|t| textures[t.texture().index()].image),
         emissive_color: crate::Color::from_rgb_alpha(mat.emissive_factor(), 0.0),
+        emissive_map: mat
+            .emissive_texture()
+            .map(|t| textures[t.texture().index()].image),
         metallic_factor: pbr.metallic_factor(),
         roughness_factor: pbr.roughness_factor(),
+        metallic_roughness_map: pbr
+            .metallic_roughness_texture()
+            .map(|t| textures[t.texture().index()].image),
         normal_scale: 1.0,
-        occlusion_strength: 1.0,
+        occlusion_strength: occlusion.as_ref().map_or(1.0, |t| t.strength()),
+        occlusion_map: occlusion.map(|t| textures[t.texture().index()].image),
     };
 
     Primitive {
@@ -123,6 +142,11 @@ fn load_primitive<'a>(
         color: crate::Color::from_rgba(base_color),
         shader: crate::pass::Shader::Gouraud { flat: true }, //TODO
         material,
+        culling: if mat.double_sided() {
+            crate::pass::Culling::None
+        } else {
+            crate::pass::Culling::Back
+        },
     }
 }
 
@@ -178,9 +202,23 @@ pub fn load_gltf(
     let mut module = Module::default();
     let (gltf, buffers, images) = gltf::import(path).expect("invalid glTF 2.0");
 
+    // Metallic-roughness and occlusion maps are not colors,
+    // so they have to be sampled without the sRGB conversion.
+    let mut linear_textures = vec![false; gltf.textures().len()];
+    for gltf_material in gltf.materials() {
+        let pbr = gltf_material.pbr_metallic_roughness();
+        if let Some(t) = pbr.metallic_roughness_texture() {
+            linear_textures[t.texture().index()] = true;
+        }
+        if let Some(t) = gltf_material.occlusion_texture() {
+            linear_textures[t.texture().index()] = true;
+        }
+    }
+
     let mut textures = Vec::with_capacity(images.len());
-    for (_texture, data) in gltf.textures().zip(images.into_iter()) {
-        let texture = load_texture(data, context);
+    for (texture, data) in gltf.textures().zip(images) {
+        let srgb = !linear_textures[texture.index()];
+        let texture = load_texture(data, srgb, context);
         textures.push(texture);
     }
 
@@ -247,6 +285,7 @@ pub fn load_gltf(
                     .component(primitive.color)
                     .component(primitive.shader)
                     .component(primitive.material)
+                    .component(primitive.culling)
                     .parent(node)
                     .build();
                 module.entities.0.push(Named {
@@ -260,10 +299,11 @@ pub fn load_gltf(
             let (depth, projection) = match gltf_camera.projection() {
                 gltf::camera::Projection::Orthographic(p) => (
                     p.znear()..p.zfar(),
-                    bc::Projection::Orthographic {
-                        center: [0.0; 2].into(),
-                        //Note: p.xmag() is ignored
-                        extent_y: p.ymag(),
+                    bc::Projection::OrthographicBounds {
+                        left: -p.xmag(),
+                        right: p.xmag(),
+                        bottom: -p.ymag(),
+                        top: p.ymag(),
                     },
                 ),
                 gltf::camera::Projection::Perspective(p) => (
@@ -286,6 +326,7 @@ pub fn load_gltf(
                     depth,
                     node,
                     background: bc::Color::default(),
+                    ..Default::default()
                 },
                 name: gltf_camera.name().map(str::to_string),
             });
@@ -0,0 +1,364 @@
+use crate::window::{Button, Event, Key};
+use std::time;
+
+/// Something moving a camera node in response to the window events.
+pub trait Controller {
+    /// Handle an event, moving the node of the scene if needed.
+    fn update(&mut self, event: &Event, scene: &mut crate::Scene);
+}
+
+/// Pointer motion between the events.
+#[derive(Default)]
+struct Pointer {
+    position: Option<glam::Vec2>,
+}
+
+impl Pointer {
+    fn delta(&mut self, position: mint::Vector2<f32>) -> glam::Vec2 {
+        let position = glam::Vec2::from(position);
+        let delta = self
+            .position
+            .map_or(glam::Vec2::ZERO, |last| position - last);
+        self.position = Some(position);
+        delta
+    }
+}
+
+/// Time between the frames, for the motion to not depend on the frame rate.
+#[derive(Default)]
+struct Clock {
+    moment: Option<time::Instant>,
+}
+
+impl Clock {
+    fn tick(&mut self) -> f32 {
+        let now = time::Instant::now();
+        let delta = self
+            .moment
+            .map_or(0.0, |moment| (now - moment).as_secs_f32());
+        self.moment = Some(now);
+        delta
+    }
+}
+
+/// Orientation with the given yaw and pitch, in radians,
+/// where zero looks along -Z with `up` being up.
+fn orientation(up: glam::Quat, yaw: f32, pitch: f32) -> glam::Quat {
+    up * glam::Quat::from_rotation_y(yaw) * glam::Quat::from_rotation_x(pitch)
+}
+
+fn up_rotation(up: mint::Vector3<f32>) -> glam::Quat {
+    glam::Quat::from_rotation_arc(glam::Vec3::Y, glam::Vec3::from(up).normalize())
+}
+
+const MAX_PITCH: f32 = 89.0;
+
+/// Orbit controller, rotating the camera around a target point.
+///
+/// Dragging with the left button rotates, dragging with the right
+/// or the middle button pans the target, and scrolling dollies the camera.
+pub struct Orbit {
+    node: crate::NodeRef,
+    up: glam::Quat,
+    /// Point the camera orbits around and looks at.
+    pub target: mint::Vector3<f32>,
+    /// Distance from the camera to the target.
+    pub distance: f32,
+    /// Angle around the up direction, in degrees.
+    pub yaw: f32,
+    /// Angle above the target, in degrees.
+    pub pitch: f32,
+    /// Degrees of rotation per pixel of the pointer motion.
+    pub rotate_speed: f32,
+    /// Panning per pixel of the pointer motion, relative to the distance.
+    pub pan_speed: f32,
+    /// Dolly per scrolled line, relative to the distance.
+    pub dolly_speed: f32,
+    /// Range of the distance kept by dollying.
+    pub distance_range: std::ops::Range<f32>,
+    pointer: Pointer,
+    rotating: bool,
+    panning: bool,
+}
+
+impl Orbit {
+    /// Orbit the camera around a target, starting from where the camera is.
+    pub fn new(
+        camera: &crate::Camera,
+        target: mint::Vector3<f32>,
+        up: mint::Vector3<f32>,
+        scene: &crate::Scene,
+    ) -> Self {
+        let up = up_rotation(up);
+        let offset = glam::Vec3::from(scene[camera.node].get_position()) - glam::Vec3::from(target);
+        let distance_range = 0.01..f32::INFINITY;
+        // a camera starting at the target looks at it along -Z
+        let dir = (up.inverse() * offset)
+            .try_normalize()
+            .unwrap_or(glam::Vec3::Z);
+        Self {
+            node: camera.node,
+            up,
+            target,
+            distance: offset.length().max(distance_range.start),
+            yaw: dir.x.atan2(dir.z).to_degrees(),
+            pitch: dir.y.asin().to_degrees(),
+            rotate_speed: 0.3,
+            pan_speed: 0.002,
+            dolly_speed: 0.1,
+            distance_range,
+            pointer: Pointer::default(),
+            rotating: false,
+            panning: false,
+        }
+    }
+
+    fn orientation(&self) -> glam::Quat {
+        // the camera above the target looks down at it
+        orientation(self.up, self.yaw.to_radians(), -self.pitch.to_radians())
+    }
+
+    /// Move the camera node to match the current parameters.
+    pub fn apply(&self, scene: &mut crate::Scene) {
+        let orientation = self.orientation();
+        let position = glam::Vec3::from(self.target) + orientation * glam::Vec3::Z * self.distance;
+        let node = &mut scene[self.node];
+        node.set_position(position.into());
+        node.set_orientation(orientation.into());
+    }
+}
+
+impl Controller for Orbit {
+    fn update(&mut self, event: &Event, scene: &mut crate::Scene) {
+        match *event {
+            Event::Click { button, pressed } => match button {
+                Button::Left => self.rotating = pressed,
+                Button::Middle | Button::Right => self.panning = pressed,
+                Button::Other(_) => {}
+            },
+            Event::Pointer { position, .. } => {
+                let delta = self.pointer.delta(position);
+                if self.rotating {
+                    self.yaw -= delta.x * self.rotate_speed;
+                    self.pitch =
+                        (self.pitch + delta.y * self.rotate_speed).clamp(-MAX_PITCH, MAX_PITCH);
+                    self.apply(scene);
+                } else if self.panning {
+                    let offset = self.orientation() * glam::Vec3::new(-delta.x, delta.y, 0.0);
+                    let target =
+                        glam::Vec3::from(self.target) + offset * self.pan_speed * self.distance;
+                    self.target = target.into();
+                    self.apply(scene);
+                }
+            }
+            Event::Scroll { delta } => {
+                let scale = (1.0 - self.dolly_speed).powf(delta.y);
+                self.distance = (self.distance * scale)
+                    .clamp(self.distance_range.start, self.distance_range.end);
+                self.apply(scene);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// First-person fly controller.
+///
+/// W/S or the up/down arrows move forward and backward, A/D or
+/// the left/right arrows strafe, E/Q move up and down. Moving the mouse
+/// with the right button held looks around, following the raw motion,
+/// so it works with the cursor grabbed. The motion speed is kept regardless
+/// of the frame rate, with the node moving on every `Event::Draw`.
+pub struct Fly {
+    node: crate::NodeRef,
+    up: glam::Quat,
+    /// Angle around the up direction, in degrees.
+    pub yaw: f32,
+    /// Angle above the horizon, in degrees.
+    pub pitch: f32,
+    /// Units per second.
+    pub move_speed: f32,
+    /// Degrees of rotation per unit of the raw mouse motion.
+    pub look_speed: f32,
+    /// Held keys, as the motion along X, Y and Z in the negative
+    /// and the positive directions.
+    held: [[bool; 2]; 3],
+    looking: bool,
+    clock: Clock,
+}
+
+impl Fly {
+    /// Fly the camera, starting from where it is and where it looks.
+    pub fn new(camera: &crate::Camera, up: mint::Vector3<f32>, scene: &crate::Scene) -> Self {
+        let up = up_rotation(up);
+        let orientation = glam::Quat::from(scene[camera.node].get_orientation());
+        let dir = up.inverse() * orientation * -glam::Vec3::Z;
+        Self {
+            node: camera.node,
+            up,
+            yaw: (-dir.x).atan2(-dir.z).to_degrees(),
+            pitch: dir.y.asin().to_degrees(),
+            move_speed: 2.0,
+            look_speed: 0.2,
+            held: [[false; 2]; 3],
+            looking: false,
+            clock: Clock::default(),
+        }
+    }
+
+    fn orientation(&self) -> glam::Quat {
+        orientation(self.up, self.yaw.to_radians(), self.pitch.to_radians())
+    }
+}
+
+impl Controller for Fly {
+    fn update(&mut self, event: &Event, scene: &mut crate::Scene) {
+        match *event {
+            Event::Keyboard { key, pressed } => {
+                let (axis, positive) = match key {
+                    Key::Letter('a') | Key::Left => (0, false),
+                    Key::Letter('d') | Key::Right => (0, true),
+                    Key::Letter('q') => (1, false),
+                    Key::Letter('e') => (1, true),
+                    Key::Letter('w') | Key::Up => (2, false),
+                    Key::Letter('s') | Key::Down => (2, true),
+                    _ => return,
+                };
+                self.held[axis][positive as usize] = pressed;
+            }
+            Event::Click {
+                button: Button::Right,
+                pressed,
+            } => {
+                self.looking = pressed;
+            }
+            Event::Focus(false) => {
+                // the keys released while unfocused are never reported
+                self.held = [[false; 2]; 3];
+                self.looking = false;
+            }
+            Event::Motion { delta } if self.looking => {
+                self.yaw -= delta.x * self.look_speed;
+                self.pitch = (self.pitch - delta.y * self.look_speed).clamp(-MAX_PITCH, MAX_PITCH);
+                scene[self.node].set_orientation(self.orientation().into());
+            }
+            Event::Draw => {
+                let time = self.clock.tick();
+                let axis = |[negative, positive]: [bool; 2]| positive as i32 - negative as i32;
+                let dir = glam::Vec3::new(
+                    axis(self.held[0]) as f32,
+                    axis(self.held[1]) as f32,
+                    axis(self.held[2]) as f32,
+                );
+                if dir != glam::Vec3::ZERO {
+                    // vertical motion follows the up direction, not the view
+                    let orientation = self.orientation();
+                    let offset = orientation * glam::Vec3::new(dir.x, 0.0, dir.z)
+                        + self.up * glam::Vec3::new(0.0, dir.y, 0.0);
+                    let node = &mut scene[self.node];
+                    let position = glam::Vec3::from(node.get_position())
+                        + offset.normalize() * self.move_speed * time;
+                    node.set_position(position.into());
+                    node.set_orientation(orientation.into());
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// 2D controller for orthographic cameras.
+///
+/// Dragging with any button pans the view, and scrolling zooms
+/// around the pointer, by scaling the camera node. The pointer mapping
+/// needs the `Event::Resize` events to know the window size.
+pub struct PanZoom {
+    node: crate::NodeRef,
+    center: glam::Vec2,
+    extent_y: f32,
+    /// Zoom per scrolled line.
+    pub zoom_speed: f32,
+    /// Range of the node scale kept by zooming, larger scales seeing more.
+    pub scale_range: std::ops::Range<f32>,
+    pointer: Pointer,
+    panning: bool,
+    size: glam::Vec2,
+}
+
+impl PanZoom {
+    /// Pan and zoom an orthographic camera.
+    pub fn new(camera: &crate::Camera) -> Self {
+        let (center, extent_y) = match camera.projection {
+            crate::Projection::Orthographic { center, extent_y } => (center.into(), extent_y),
+            crate::Projection::OrthographicBounds {
+                left,
+                right,
+                bottom,
+                top,
+            } => (
+                glam::Vec2::new(0.5 * (left + right), 0.5 * (bottom + top)),
+                0.5 * (top - bottom),
+            ),
+            _ => panic!("Pan-zoom needs an orthographic camera"),
+        };
+        Self {
+            node: camera.node,
+            center,
+            extent_y,
+            zoom_speed: 0.1,
+            scale_range: 0.001..1000.0,
+            pointer: Pointer::default(),
+            panning: false,
+            size: glam::Vec2::ZERO,
+        }
+    }
+
+    /// Units of the view space per pixel, at the scale of 1.
+    fn units_per_pixel(&self) -> f32 {
+        if self.size.y > 0.0 {
+            2.0 * self.extent_y / self.size.y
+        } else {
+            0.0
+        }
+    }
+}
+
+impl Controller for PanZoom {
+    fn update(&mut self, event: &Event, scene: &mut crate::Scene) {
+        match *event {
+            Event::Resize { width, height } => {
+                self.size = glam::Vec2::new(width as f32, height as f32);
+            }
+            Event::Click { pressed, .. } => {
+                self.panning = pressed;
+            }
+            Event::Pointer { position, .. } => {
+                let delta = self.pointer.delta(position);
+                if self.panning {
+                    let node = &mut scene[self.node];
+                    let orientation = glam::Quat::from(node.get_orientation());
+                    let offset = orientation * glam::Vec3::new(-delta.x, delta.y, 0.0);
+                    let position = glam::Vec3::from(node.get_position())
+                        + offset * self.units_per_pixel() * node.get_scale();
+                    node.set_position(position.into());
+                }
+            }
+            Event::Scroll { delta } => {
+                let node = &mut scene[self.node];
+                let scale = node.get_scale();
+                let new_scale = (scale * (1.0 - self.zoom_speed).powf(delta.y))
+                    .clamp(self.scale_range.start, self.scale_range.end);
+                // keep the point under the pointer in place
+                let pointer = self.pointer.position.unwrap_or(0.5 * self.size) - 0.5 * self.size;
+                let view_point =
+                    self.center + glam::Vec2::new(pointer.x, -pointer.y) * self.units_per_pixel();
+                let orientation = glam::Quat::from(node.get_orientation());
+                let offset = orientation * view_point.extend(0.0) * (scale - new_scale);
+                let position = glam::Vec3::from(node.get_position()) + offset;
+                node.set_position(position.into());
+                node.set_scale(new_scale);
+            }
+            _ => {}
+        }
+    }
+}
@@ -75,6 +75,7 @@ impl super::Geometry {
                 radius,
                 positions,
                 normals: Some(normals),
+                tex_coords: None,
                 indices: Some(indices),
             }
         } else {
@@ -104,6 +105,7 @@ impl super::Geometry {
                 radius,
                 positions,
                 normals: None,
+                tex_coords: None,
                 indices: Some(indices),
             }
         }
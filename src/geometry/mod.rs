@@ -14,6 +14,7 @@ bitflags::bitflags!(
 pub struct Geometry {
     pub positions: Vec<crate::Position>,
     pub normals: Option<Vec<crate::Normal>>,
+    pub tex_coords: Option<Vec<crate::TexCoords>>,
     pub indices: Option<Vec<u16>>,
     pub radius: f32,
 }
@@ -26,6 +27,9 @@ impl Geometry {
         if let Some(ref stream) = self.normals {
             mb.vertex(stream);
         }
+        if let Some(ref stream) = self.tex_coords {
+            mb.vertex(stream);
+        }
         if let Some(ref indices) = self.indices {
             mb.index(indices);
         }
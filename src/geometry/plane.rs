@@ -1,4 +1,4 @@
-use crate::{Normal, Position};
+use crate::{Normal, Position, TexCoords};
 
 impl super::Geometry {
     pub fn plane(size: f32) -> Self {
@@ -17,17 +17,21 @@ impl super::Geometry {
 
         let mut positions = Vec::new();
         let mut normals = Vec::new();
-        // let mut uvs = Vec::new();
-        for (position, normal, _uv) in vertices.iter() {
-            positions.push(Position(*position));
-            normals.push(Normal(*normal));
-            // uvs.push(*uv);
+        let mut tex_coords = Vec::new();
+        for &(position, normal, uv) in vertices.iter() {
+            positions.push(Position(position));
+            normals.push(Normal(normal));
+            tex_coords.push(TexCoords([
+                (uv[0] * 65535.0) as u16,
+                (uv[1] * 65535.0) as u16,
+            ]));
         }
 
         Self {
             radius,
             positions,
             normals: Some(normals),
+            tex_coords: Some(tex_coords),
             indices: Some(indices),
         }
     }
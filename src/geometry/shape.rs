@@ -35,6 +35,7 @@ impl super::Geometry {
             positions: buffer.vertices,
             indices: Some(buffer.indices),
             normals: None,
+            tex_coords: None,
             radius,
         }
     }
@@ -51,6 +52,7 @@ impl super::Geometry {
             positions: buffer.vertices,
             indices: Some(buffer.indices),
             normals: None,
+            tex_coords: None,
             radius,
         }
     }
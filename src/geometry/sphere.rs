@@ -105,6 +105,7 @@ impl super::Geometry {
         Self {
             positions,
             normals,
+            tex_coords: None,
             radius,
             indices: Some(indices),
         }
@@ -1,11 +1,14 @@
 pub use bc::{
-    Camera, Color, Context, Entity, EntityRef, ImageRef, Light, LightBuilder, LightRef,
-    MeshBuilder, MeshRef, Node, NodeRef, Pass, Projection, Prototype, Scene, Sprite, SpriteBuilder,
-    TargetInfo, TargetRef, UvRange,
+    Camera, Color, Context, Entity, EntityRef, Eye, Frame, ImageRef, Light, LightBuilder, LightRef,
+    Load, LoadOps, MeshBuilder, MeshRef, Node, NodeRef, Offscreen, Pass, PassId, Projection,
+    Prototype, RenderGraph, Resource, Scene, Sprite, SpriteBuilder, Stereo, TargetInfo, TargetRef,
+    UvRange, Viewport, Visibility, MAX_CLIP_PLANES,
 };
 use std::mem;
 
 pub mod asset;
+#[cfg(feature = "window")]
+pub mod control;
 pub mod geometry;
 pub mod pass;
 #[cfg(feature = "window")]
@@ -0,0 +1,209 @@
+use bc::ContextDetail as _;
+use std::mem;
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct Globals {
+    inv_view_proj: [[f32; 4]; 4],
+    sun_dir: [f32; 4],
+    sun_color: [f32; 4],
+    turbidity: f32,
+    luminance_scale: f32,
+    pad: [f32; 2],
+}
+
+#[derive(Clone, Debug)]
+pub struct AtmosphereConfig {
+    /// Directional light acting as the sun.
+    /// If `None`, the first directional light of the scene is used.
+    pub sun: Option<crate::LightRef>,
+    /// Haziness of the air, from 2 for a clear sky to 10 for a foggy one.
+    pub turbidity: f32,
+    /// Conversion from the model luminance (in kcd/m²) to the output color.
+    pub luminance_scale: f32,
+    /// Angular radius of the sun disk, in radians.
+    pub sun_radius: f32,
+}
+
+impl Default for AtmosphereConfig {
+    fn default() -> Self {
+        Self {
+            sun: None,
+            turbidity: 2.5,
+            luminance_scale: 0.05,
+            sun_radius: 0.005,
+        }
+    }
+}
+
+/// Background pass, drawing an analytic daylight sky.
+///
+/// Uses the Preetham model, with the sun direction and color
+/// taken from a directional light of the scene.
+pub struct Atmosphere {
+    global_uniform_buf: wgpu::Buffer,
+    global_bind_group: wgpu::BindGroup,
+    shader_module: wgpu::ShaderModule,
+    pipeline_layout: wgpu::PipelineLayout,
+    pipelines: super::FullscreenPipelines,
+    config: AtmosphereConfig,
+    load_ops: bc::LoadOps,
+}
+
+impl Atmosphere {
+    pub fn new(config: &AtmosphereConfig, context: &crate::Context) -> Self {
+        Self::new_offscreen(config, context.surface_info().unwrap(), context)
+    }
+    pub fn new_offscreen(
+        config: &AtmosphereConfig,
+        target_info: crate::TargetInfo,
+        context: &crate::Context,
+    ) -> Self {
+        let d = context.device();
+        let shader_module = d.create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label: Some("atmosphere"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("atmosphere.wgsl").into()),
+        });
+
+        let globals_size = mem::size_of::<Globals>() as wgpu::BufferAddress;
+        let global_bgl = d.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("atmosphere globals"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: wgpu::BufferSize::new(globals_size),
+                },
+                count: None,
+            }],
+        });
+        let global_uniform_buf = d.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("atmosphere globals"),
+            size: globals_size,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let global_bind_group = d.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("atmosphere globals"),
+            layout: &global_bgl,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: global_uniform_buf.as_entire_binding(),
+            }],
+        });
+
+        let pipeline_layout = d.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("atmosphere"),
+            bind_group_layouts: &[&global_bgl],
+            push_constant_ranges: &[],
+        });
+        let mut pipelines = super::FullscreenPipelines::new("atmosphere", "main_fs");
+        pipelines.get(
+            super::TargetKey::from_info(&target_info),
+            &pipeline_layout,
+            &shader_module,
+            d,
+        );
+
+        Self {
+            global_uniform_buf,
+            global_bind_group,
+            shader_module,
+            pipeline_layout,
+            pipelines,
+            config: config.clone(),
+            load_ops: bc::LoadOps::default(),
+        }
+    }
+
+    /// Choose whether the sky clears the target color first.
+    /// The pass has no depth, so the depth load is ignored.
+    pub fn set_load_ops(&mut self, load_ops: bc::LoadOps) {
+        self.load_ops = load_ops;
+    }
+}
+
+impl bc::Pass for Atmosphere {
+    fn draw(
+        &mut self,
+        targets: &[crate::TargetRef],
+        scene: &crate::Scene,
+        camera: &crate::Camera,
+        context: &crate::Context,
+    ) {
+        super::assert_mono(camera, "Atmosphere");
+        let target = context.get_target(targets[0]);
+        let device = context.device();
+        let queue = context.queue();
+
+        {
+            let nodes = scene.bake();
+            let m_proj = glam::Mat4::from(camera.target_projection_matrix(target.size));
+            let m_rot = glam::Mat4::from_quat(glam::Quat::from_array(nodes[camera.node].rot));
+
+            let sun = match self.config.sun {
+                Some(light) => Some(&scene.lights[light]),
+                None => scene
+                    .lights()
+                    .map(|(_, light)| light)
+                    .find(|light| matches!(light.kind, bc::LightKind::Directional)),
+            };
+            // without a sun, keep the sky lit from the zenith
+            let (sun_dir, sun_color) = match sun {
+                Some(light) => {
+                    let mut color = light.color.into_vec4();
+                    for c in color[..3].iter_mut() {
+                        *c *= light.intensity;
+                    }
+                    (nodes[light.node].pos_scale, color)
+                }
+                None => ([0.0, 1.0, 0.0, 0.0], [0.0; 4]),
+            };
+
+            let globals = Globals {
+                inv_view_proj: (m_rot * m_proj.inverse()).to_cols_array_2d(),
+                sun_dir,
+                sun_color: [
+                    sun_color[0],
+                    sun_color[1],
+                    sun_color[2],
+                    self.config.sun_radius.cos(),
+                ],
+                turbidity: self.config.turbidity,
+                luminance_scale: self.config.luminance_scale,
+                pad: [0.0; 2],
+            };
+            queue.write_buffer(&self.global_uniform_buf, 0, bytemuck::bytes_of(&globals));
+        }
+
+        let pipeline = self.pipelines.get(
+            super::TargetKey::new(target),
+            &self.pipeline_layout,
+            &self.shader_module,
+            device,
+        );
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("atmosphere"),
+                color_attachments: &[wgpu::RenderPassColorAttachment {
+                    view: &target.view,
+                    resolve_target: target.resolve_view.as_ref(),
+                    ops: wgpu::Operations {
+                        load: self.load_ops.color_op(camera.background),
+                        store: true,
+                    },
+                }],
+                depth_stencil_attachment: None,
+            });
+            camera.apply_viewport(&mut pass, target.size);
+            pass.set_pipeline(pipeline);
+            pass.set_bind_group(0, &self.global_bind_group, &[]);
+            pass.draw(0..3, 0..1);
+        }
+
+        context.submit(encoder.finish());
+    }
+}
@@ -0,0 +1,104 @@
+struct Globals {
+    inv_view_proj: mat4x4<f32>;
+    // XYZ is the direction towards the sun
+    sun_dir: vec4<f32>;
+    // RGB is the sun disk color, A is the cosine of its angular radius
+    sun_color: vec4<f32>;
+    turbidity: f32;
+    luminance_scale: f32;
+    pad: vec2<f32>;
+};
+[[group(0), binding(0)]]
+var<uniform> globals: Globals;
+
+let PI: f32 = 3.141592653589793;
+
+struct Varyings {
+    [[builtin(position)]] clip_pos: vec4<f32>;
+    [[location(0)]] dir: vec3<f32>;
+};
+
+[[stage(vertex)]]
+fn main_vs([[builtin(vertex_index)]] index: u32) -> Varyings {
+    let pos = vec2<f32>(
+        f32(i32(index & 1u) * 4 - 1),
+        f32(i32(index & 2u) * 2 - 1),
+    );
+    let world = globals.inv_view_proj * vec4<f32>(pos, 0.5, 1.0);
+    return Varyings(
+        vec4<f32>(pos, 0.0, 1.0),
+        world.xyz / world.w,
+    );
+}
+
+struct Perez {
+    a: vec3<f32>;
+    b: vec3<f32>;
+    c: vec3<f32>;
+    d: vec3<f32>;
+    e: vec3<f32>;
+};
+
+// Distribution coefficients for the chromaticities x, y and the luminance Y,
+// from "A Practical Analytic Model for Daylight" by A. J. Preetham et al.
+fn perez_coefficients(t: f32) -> Perez {
+    return Perez(
+        vec3<f32>(-0.0193 * t - 0.2592, -0.0167 * t - 0.2608, 0.1787 * t - 1.4630),
+        vec3<f32>(-0.0665 * t + 0.0008, -0.0950 * t + 0.0092, -0.3554 * t + 0.4275),
+        vec3<f32>(-0.0004 * t + 0.2125, -0.0079 * t + 0.2102, -0.0227 * t + 5.3251),
+        vec3<f32>(-0.0641 * t - 0.8989, -0.0441 * t - 1.6537, 0.1206 * t - 2.5771),
+        vec3<f32>(-0.0033 * t + 0.0452, -0.0109 * t + 0.0529, -0.0670 * t + 0.3703),
+    );
+}
+
+fn perez(p: Perez, cos_theta: f32, gamma: f32) -> vec3<f32> {
+    let cos_gamma = cos(gamma);
+    return (1.0 + p.a * exp(p.b / cos_theta)) * (1.0 + p.c * exp(p.d * gamma) + p.e * cos_gamma * cos_gamma);
+}
+
+fn zenith_xyy(t: f32, theta_sun: f32) -> vec3<f32> {
+    let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * theta_sun);
+    let big_y = (4.0453 * t - 4.9710) * tan(chi) - 0.2155 * t + 2.4192;
+    let th = vec4<f32>(theta_sun * theta_sun * theta_sun, theta_sun * theta_sun, theta_sun, 1.0);
+    let x = t * t * dot(th, vec4<f32>(0.00166, -0.00375, 0.00209, 0.0))
+        + t * dot(th, vec4<f32>(-0.02903, 0.06377, -0.03202, 0.00394))
+        + dot(th, vec4<f32>(0.11693, -0.21196, 0.06052, 0.25886));
+    let y = t * t * dot(th, vec4<f32>(0.00275, -0.00610, 0.00317, 0.0))
+        + t * dot(th, vec4<f32>(-0.04214, 0.08970, -0.04153, 0.00516))
+        + dot(th, vec4<f32>(0.15346, -0.26756, 0.06670, 0.26688));
+    return vec3<f32>(x, y, big_y);
+}
+
+fn xyy_to_linear_srgb(c: vec3<f32>) -> vec3<f32> {
+    let big_x = c.x * c.z / c.y;
+    let big_z = (1.0 - c.x - c.y) * c.z / c.y;
+    return vec3<f32>(
+        3.2406 * big_x - 1.5372 * c.z - 0.4986 * big_z,
+        -0.9689 * big_x + 1.8758 * c.z + 0.0415 * big_z,
+        0.0557 * big_x - 0.2040 * c.z + 1.0570 * big_z,
+    );
+}
+
+[[stage(fragment)]]
+fn main_fs(in: Varyings) -> [[location(0)]] vec4<f32> {
+    let dir = normalize(in.dir);
+    let sun = normalize(globals.sun_dir.xyz);
+    // the model is only defined above the horizon
+    let cos_theta = max(dir.y, 0.01);
+    let cos_theta_sun = max(sun.y, 0.0);
+    let theta_sun = acos(cos_theta_sun);
+    let gamma = acos(clamp(dot(dir, sun), -1.0, 1.0));
+
+    let p = perez_coefficients(globals.turbidity);
+    let zenith = zenith_xyy(globals.turbidity, theta_sun);
+    let xyy = zenith * perez(p, cos_theta, gamma) / perez(p, 1.0, theta_sun);
+    // fade out the sky as the sun goes below the horizon
+    let night = smoothStep(-0.1, 0.05, sun.y);
+    let luminance = xyy.z * globals.luminance_scale * night;
+    var color = max(xyy_to_linear_srgb(vec3<f32>(xyy.xy, luminance)), vec3<f32>(0.0));
+
+    if (dot(dir, sun) > globals.sun_color.w && dir.y > 0.0) {
+        color = color + globals.sun_color.xyz;
+    }
+    return vec4<f32>(color, 1.0);
+}
@@ -0,0 +1,381 @@
+use bc::ContextDetail as _;
+use std::{mem, num::NonZeroU32};
+
+const CHAIN_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct Params {
+    threshold: f32,
+    knee: f32,
+    intensity: f32,
+    pad: f32,
+}
+
+#[derive(Debug)]
+pub struct BloomConfig {
+    /// Brightness above which the colors start to bleed.
+    pub threshold: f32,
+    /// Width of the soft transition around the threshold.
+    pub knee: f32,
+    /// Strength of the glow added to the image.
+    pub intensity: f32,
+    /// Maximum number of blur levels, each at half the resolution
+    /// of the previous one. More levels give a wider glow.
+    pub max_levels: u32,
+}
+
+impl Default for BloomConfig {
+    fn default() -> Self {
+        Self {
+            threshold: 1.0,
+            knee: 0.5,
+            intensity: 0.3,
+            max_levels: 6,
+        }
+    }
+}
+
+struct Pipelines {
+    prefilter: wgpu::RenderPipeline,
+    downsample: wgpu::RenderPipeline,
+    upsample: wgpu::RenderPipeline,
+}
+
+struct Chain {
+    size: wgpu::Extent3d,
+    views: Vec<wgpu::TextureView>,
+    bind_groups: Vec<wgpu::BindGroup>,
+}
+
+/// Post-processing pass that makes bright areas glow.
+///
+/// Reads the first target and adds the glow back into it,
+/// so it can follow any pass drawing into an HDR target.
+/// The target has to be sampleable, e.g. created by `Context::add_target`,
+/// so it can't be the surface.
+pub struct Bloom {
+    params_buf: wgpu::Buffer,
+    sampler: wgpu::Sampler,
+    bind_group_layout: wgpu::BindGroupLayout,
+    shader_module: wgpu::ShaderModule,
+    pipeline_layout: wgpu::PipelineLayout,
+    pipelines: Pipelines,
+    composite_pipelines: super::FullscreenPipelines,
+    chain: Option<Chain>,
+    target_bind_group: Option<(wgpu::BindGroup, crate::TargetRef, wgpu::Extent3d)>,
+    max_levels: u32,
+}
+
+fn draw_fullscreen(
+    encoder: &mut wgpu::CommandEncoder,
+    view: &wgpu::TextureView,
+    resolve_target: Option<&wgpu::TextureView>,
+    load: wgpu::LoadOp<wgpu::Color>,
+    pipeline: &wgpu::RenderPipeline,
+    bind_group: &wgpu::BindGroup,
+) {
+    let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some("bloom"),
+        color_attachments: &[wgpu::RenderPassColorAttachment {
+            view,
+            resolve_target,
+            ops: wgpu::Operations { load, store: true },
+        }],
+        depth_stencil_attachment: None,
+    });
+    pass.set_pipeline(pipeline);
+    pass.set_bind_group(0, bind_group, &[]);
+    pass.draw(0..3, 0..1);
+}
+
+impl Bloom {
+    pub fn new_offscreen(
+        config: &BloomConfig,
+        target_info: crate::TargetInfo,
+        context: &crate::Context,
+    ) -> Self {
+        let d = context.device();
+        let shader_module = d.create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label: Some("bloom"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("bloom.wgsl").into()),
+        });
+
+        let params_size = mem::size_of::<Params>() as wgpu::BufferAddress;
+        let params_buf = d.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("bloom params"),
+            size: params_size,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let sampler = d.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("bloom sampler"),
+            min_filter: wgpu::FilterMode::Linear,
+            mag_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let bind_group_layout = d.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("bloom"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: wgpu::BufferSize::new(params_size),
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let pipeline_layout = d.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("bloom"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let additive = wgpu::BlendState {
+            color: wgpu::BlendComponent {
+                src_factor: wgpu::BlendFactor::One,
+                dst_factor: wgpu::BlendFactor::One,
+                operation: wgpu::BlendOperation::Add,
+            },
+            alpha: wgpu::BlendComponent::REPLACE,
+        };
+        let pipelines = {
+            let create_pipeline = |entry_point, blend| {
+                d.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                    label: Some(entry_point),
+                    layout: Some(&pipeline_layout),
+                    vertex: wgpu::VertexState {
+                        buffers: &[],
+                        module: &shader_module,
+                        entry_point: "main_vs",
+                    },
+                    primitive: wgpu::PrimitiveState::default(),
+                    depth_stencil: None,
+                    multisample: wgpu::MultisampleState::default(),
+                    fragment: Some(wgpu::FragmentState {
+                        targets: &[wgpu::ColorTargetState {
+                            format: CHAIN_FORMAT,
+                            blend,
+                            write_mask: wgpu::ColorWrites::COLOR,
+                        }],
+                        module: &shader_module,
+                        entry_point,
+                    }),
+                    multiview: None,
+                })
+            };
+
+            Pipelines {
+                prefilter: create_pipeline("prefilter_fs", None),
+                downsample: create_pipeline("downsample_fs", None),
+                upsample: create_pipeline("upsample_fs", Some(additive)),
+            }
+        };
+        // the glow is added to the target, keeping its alpha
+        let mut composite_pipelines =
+            super::FullscreenPipelines::new("composite_fs", "composite_fs")
+                .with_blend(additive, wgpu::ColorWrites::COLOR);
+        composite_pipelines.get(
+            super::TargetKey::from_info(&target_info),
+            &pipeline_layout,
+            &shader_module,
+            d,
+        );
+
+        let this = Self {
+            params_buf,
+            sampler,
+            bind_group_layout,
+            shader_module,
+            pipeline_layout,
+            pipelines,
+            composite_pipelines,
+            chain: None,
+            target_bind_group: None,
+            max_levels: config.max_levels.max(1),
+        };
+        this.set_params(config, context);
+        this
+    }
+
+    fn set_params(&self, config: &BloomConfig, context: &crate::Context) {
+        let params = Params {
+            threshold: config.threshold,
+            knee: config.knee.max(0.0),
+            intensity: config.intensity,
+            pad: 0.0,
+        };
+        context
+            .queue()
+            .write_buffer(&self.params_buf, 0, bytemuck::bytes_of(&params));
+    }
+
+    /// Change the bloom parameters.
+    pub fn configure(&mut self, config: &BloomConfig, context: &crate::Context) {
+        self.set_params(config, context);
+        if self.max_levels != config.max_levels.max(1) {
+            self.max_levels = config.max_levels.max(1);
+            self.chain = None;
+        }
+    }
+
+    fn create_bind_group(
+        &self,
+        view: &wgpu::TextureView,
+        device: &wgpu::Device,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("bloom"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: self.params_buf.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(view),
+                },
+            ],
+        })
+    }
+}
+
+impl bc::Pass for Bloom {
+    fn draw(
+        &mut self,
+        targets: &[crate::TargetRef],
+        _scene: &crate::Scene,
+        _camera: &crate::Camera,
+        context: &crate::Context,
+    ) {
+        let target = context.get_target(targets[0]);
+        let device = context.device();
+
+        let reset_chain = match self.chain {
+            Some(ref chain) => chain.size != target.size,
+            None => true,
+        };
+        if reset_chain {
+            let size = wgpu::Extent3d {
+                width: (target.size.width / 2).max(1),
+                height: (target.size.height / 2).max(1),
+                depth_or_array_layers: 1,
+            };
+            let max_mips = 32 - size.width.min(size.height).leading_zeros();
+            let level_count = self.max_levels.min(max_mips);
+            let texture = device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("bloom chain"),
+                size,
+                mip_level_count: level_count,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: CHAIN_FORMAT,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                    | wgpu::TextureUsages::TEXTURE_BINDING,
+            });
+            let views = (0..level_count)
+                .map(|level| {
+                    texture.create_view(&wgpu::TextureViewDescriptor {
+                        base_mip_level: level,
+                        mip_level_count: NonZeroU32::new(1),
+                        ..Default::default()
+                    })
+                })
+                .collect::<Vec<_>>();
+            let bind_groups = views
+                .iter()
+                .map(|view| self.create_bind_group(view, device))
+                .collect();
+            self.chain = Some(Chain {
+                size: target.size,
+                views,
+                bind_groups,
+            });
+        }
+        // the target view is re-created when it's resized
+        let reset_target_bind_group = match self.target_bind_group {
+            Some((_, tr, size)) => tr != targets[0] || size != target.size,
+            None => true,
+        };
+        if reset_target_bind_group {
+            let bind_group = self.create_bind_group(target.resolved_view(), device);
+            self.target_bind_group = Some((bind_group, targets[0], target.size));
+        }
+        let chain = self.chain.as_ref().unwrap();
+        let target_bind_group = &self.target_bind_group.as_ref().unwrap().0;
+        let clear = wgpu::LoadOp::Clear(wgpu::Color::BLACK);
+
+        let composite_pipeline = self.composite_pipelines.get(
+            super::TargetKey::new(target),
+            &self.pipeline_layout,
+            &self.shader_module,
+            device,
+        );
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        draw_fullscreen(
+            &mut encoder,
+            &chain.views[0],
+            None,
+            clear,
+            &self.pipelines.prefilter,
+            target_bind_group,
+        );
+        for level in 1..chain.views.len() {
+            draw_fullscreen(
+                &mut encoder,
+                &chain.views[level],
+                None,
+                clear,
+                &self.pipelines.downsample,
+                &chain.bind_groups[level - 1],
+            );
+        }
+        for level in (1..chain.views.len()).rev() {
+            draw_fullscreen(
+                &mut encoder,
+                &chain.views[level - 1],
+                None,
+                wgpu::LoadOp::Load,
+                &self.pipelines.upsample,
+                &chain.bind_groups[level],
+            );
+        }
+        draw_fullscreen(
+            &mut encoder,
+            &target.view,
+            target.resolve_view.as_ref(),
+            wgpu::LoadOp::Load,
+            composite_pipeline,
+            &chain.bind_groups[0],
+        );
+
+        context.submit(encoder.finish());
+    }
+}
@@ -0,0 +1,83 @@
+struct Params {
+    threshold: f32;
+    knee: f32;
+    intensity: f32;
+};
+[[group(0), binding(0)]]
+var<uniform> params: Params;
+
+[[group(0), binding(1)]]
+var sam: sampler;
+[[group(0), binding(2)]]
+var source: texture_2d<f32>;
+
+struct Varyings {
+    [[builtin(position)]] clip_pos: vec4<f32>;
+    [[location(0)]] uv: vec2<f32>;
+};
+
+[[stage(vertex)]]
+fn main_vs([[builtin(vertex_index)]] index: u32) -> Varyings {
+    let pos = vec2<f32>(
+        f32(i32(index & 1u) * 4 - 1),
+        f32(i32(index & 2u) * 2 - 1),
+    );
+    return Varyings(
+        vec4<f32>(pos, 0.0, 1.0),
+        vec2<f32>(0.5 * pos.x + 0.5, 0.5 - 0.5 * pos.y),
+    );
+}
+
+// Samples a 4x4 texel box with 4 bilinear fetches.
+fn sample_box(uv: vec2<f32>) -> vec3<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(source, 0));
+    let d = vec4<f32>(-1.0, -1.0, 1.0, 1.0) * texel.xyxy;
+    return 0.25 * (
+        textureSample(source, sam, uv + d.xy).xyz +
+        textureSample(source, sam, uv + d.zy).xyz +
+        textureSample(source, sam, uv + d.xw).xyz +
+        textureSample(source, sam, uv + d.zw).xyz
+    );
+}
+
+// Samples a 3x3 tent filter.
+fn sample_tent(uv: vec2<f32>) -> vec3<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(source, 0));
+    let d = vec4<f32>(1.0, 1.0, -1.0, 0.0) * texel.xyxy;
+    var sum = textureSample(source, sam, uv - d.xy).xyz;
+    sum = sum + textureSample(source, sam, uv - d.wy).xyz * 2.0;
+    sum = sum + textureSample(source, sam, uv - d.zy).xyz;
+    sum = sum + textureSample(source, sam, uv + d.zw).xyz * 2.0;
+    sum = sum + textureSample(source, sam, uv).xyz * 4.0;
+    sum = sum + textureSample(source, sam, uv + d.xw).xyz * 2.0;
+    sum = sum + textureSample(source, sam, uv + d.zy).xyz;
+    sum = sum + textureSample(source, sam, uv + d.wy).xyz * 2.0;
+    sum = sum + textureSample(source, sam, uv + d.xy).xyz;
+    return sum / 16.0;
+}
+
+[[stage(fragment)]]
+fn prefilter_fs(in: Varyings) -> [[location(0)]] vec4<f32> {
+    let color = sample_box(in.uv);
+    // soft threshold, with a quadratic curve around the knee
+    let brightness = max(color.x, max(color.y, color.z));
+    let soft = clamp(brightness - params.threshold + params.knee, 0.0, 2.0 * params.knee);
+    let curve = soft * soft / (4.0 * params.knee + 0.0001);
+    let contribution = max(curve, brightness - params.threshold) / max(brightness, 0.0001);
+    return vec4<f32>(color * contribution, 1.0);
+}
+
+[[stage(fragment)]]
+fn downsample_fs(in: Varyings) -> [[location(0)]] vec4<f32> {
+    return vec4<f32>(sample_box(in.uv), 1.0);
+}
+
+[[stage(fragment)]]
+fn upsample_fs(in: Varyings) -> [[location(0)]] vec4<f32> {
+    return vec4<f32>(sample_tent(in.uv), 1.0);
+}
+
+[[stage(fragment)]]
+fn composite_fs(in: Varyings) -> [[location(0)]] vec4<f32> {
+    return vec4<f32>(params.intensity * sample_tent(in.uv), 0.0);
+}
@@ -0,0 +1,339 @@
+use bc::ContextDetail as _;
+use std::{mem, num::NonZeroU32};
+
+const CUBE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+const BRDF_LUT_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rg16Float;
+// Has to match `ENVIRONMENT_SIZE` in the shader.
+const ENVIRONMENT_SIZE: u32 = 256;
+const IRRADIANCE_SIZE: u32 = 32;
+const SPECULAR_SIZE: u32 = 128;
+const SPECULAR_MIP_COUNT: u32 = 5;
+const BRDF_LUT_SIZE: u32 = 128;
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct Params {
+    face: u32,
+    roughness: f32,
+}
+
+fn create_cube(
+    label: &str,
+    size: u32,
+    mip_level_count: u32,
+    device: &wgpu::Device,
+) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some(label),
+        size: wgpu::Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: 6,
+        },
+        mip_level_count,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: CUBE_FORMAT,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+    })
+}
+
+fn create_face_view(texture: &wgpu::Texture, face: u32, mip: u32) -> wgpu::TextureView {
+    texture.create_view(&wgpu::TextureViewDescriptor {
+        dimension: Some(wgpu::TextureViewDimension::D2),
+        base_mip_level: mip,
+        mip_level_count: NonZeroU32::new(1),
+        base_array_layer: face,
+        array_layer_count: NonZeroU32::new(1),
+        ..Default::default()
+    })
+}
+
+fn draw_fullscreen(
+    encoder: &mut wgpu::CommandEncoder,
+    view: &wgpu::TextureView,
+    pipeline: &wgpu::RenderPipeline,
+    bind_group: Option<(&wgpu::BindGroup, wgpu::DynamicOffset)>,
+) {
+    let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some("environment"),
+        color_attachments: &[wgpu::RenderPassColorAttachment {
+            view,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                store: true,
+            },
+        }],
+        depth_stencil_attachment: None,
+    });
+    pass.set_pipeline(pipeline);
+    if let Some((bg, offset)) = bind_group {
+        pass.set_bind_group(0, bg, &[offset]);
+    }
+    pass.draw(0..3, 0..1);
+}
+
+/// Pre-filtered environment lighting, used for image-based lighting.
+///
+/// Contains the diffuse irradiance and the specular radiance cube maps,
+/// as well as the BRDF integration lookup table.
+pub struct Environment {
+    pub(super) irradiance_view: wgpu::TextureView,
+    pub(super) specular_view: wgpu::TextureView,
+    pub(super) brdf_lut_view: wgpu::TextureView,
+    pub(super) specular_mip_count: u32,
+}
+
+impl Environment {
+    /// Bake the environment from a panorama.
+    ///
+    /// The image is expected to be in linear color space,
+    /// preferably HDR, such as the ones loaded from ".hdr" files.
+    pub fn new(panorama: super::Panorama, context: &crate::Context) -> Self {
+        let d = context.device();
+        let shader_module = d.create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label: Some("environment"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("environment.wgsl").into()),
+        });
+
+        let params_entry = wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: true,
+                min_binding_size: wgpu::BufferSize::new(
+                    mem::size_of::<Params>() as wgpu::BufferAddress
+                ),
+            },
+            count: None,
+        };
+        let sampler_entry = wgpu::BindGroupLayoutEntry {
+            binding: 1,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+            count: None,
+        };
+        let equirect_bgl = d.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("environment equirect"),
+            entries: &[
+                params_entry,
+                sampler_entry,
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let cube_bgl = d.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("environment cube"),
+            entries: &[
+                params_entry,
+                sampler_entry,
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::Cube,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let sampler = d.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("environment sampler"),
+            address_mode_u: wgpu::AddressMode::Repeat,
+            min_filter: wgpu::FilterMode::Linear,
+            mag_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let create_pipeline = |entry_point: &str,
+                               bind_group_layouts: &[&wgpu::BindGroupLayout],
+                               format: wgpu::TextureFormat| {
+            let layout = d.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some(entry_point),
+                bind_group_layouts,
+                push_constant_ranges: &[],
+            });
+            d.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(entry_point),
+                layout: Some(&layout),
+                vertex: wgpu::VertexState {
+                    buffers: &[],
+                    module: &shader_module,
+                    entry_point: "main_vs",
+                },
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                fragment: Some(wgpu::FragmentState {
+                    targets: &[format.into()],
+                    module: &shader_module,
+                    entry_point,
+                }),
+                multiview: None,
+            })
+        };
+        let equirect_pipeline = create_pipeline("equirect_fs", &[&equirect_bgl], CUBE_FORMAT);
+        let downsample_pipeline = create_pipeline("downsample_fs", &[&cube_bgl], CUBE_FORMAT);
+        let irradiance_pipeline = create_pipeline("irradiance_fs", &[&cube_bgl], CUBE_FORMAT);
+        let specular_pipeline = create_pipeline("specular_fs", &[&cube_bgl], CUBE_FORMAT);
+        let brdf_pipeline = create_pipeline("brdf_fs", &[], BRDF_LUT_FORMAT);
+
+        let environment_mip_count = 32 - ENVIRONMENT_SIZE.leading_zeros();
+        let environment = create_cube("environment", ENVIRONMENT_SIZE, environment_mip_count, d);
+        let irradiance = create_cube("irradiance", IRRADIANCE_SIZE, 1, d);
+        let specular = create_cube("specular", SPECULAR_SIZE, SPECULAR_MIP_COUNT, d);
+        let brdf_lut = d.create_texture(&wgpu::TextureDescriptor {
+            label: Some("BRDF LUT"),
+            size: wgpu::Extent3d {
+                width: BRDF_LUT_SIZE,
+                height: BRDF_LUT_SIZE,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: BRDF_LUT_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        });
+        let environment_view = environment.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::Cube),
+            ..Default::default()
+        });
+
+        let draw_count = 6 * (environment_mip_count + 1 + SPECULAR_MIP_COUNT) as usize;
+        let mut uniform_pool = super::BufferPool::uniform("environment params", d);
+        uniform_pool.prepare_for_count::<Params>(draw_count, d);
+        let queue = context.queue();
+        let mut encoder = d.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+
+        let mut draw_faces = |encoder: &mut wgpu::CommandEncoder,
+                              texture: &wgpu::Texture,
+                              mip: u32,
+                              roughness: f32,
+                              pipeline: &wgpu::RenderPipeline,
+                              layout: &wgpu::BindGroupLayout,
+                              source_view: &wgpu::TextureView,
+                              source_binding: u32| {
+            for face in 0..6 {
+                let bl = uniform_pool.alloc(&Params { face, roughness }, queue);
+                let bind_group = d.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("environment"),
+                    layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: wgpu::BindingResource::Buffer(
+                                uniform_pool.binding::<Params>(bl.index),
+                            ),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: wgpu::BindingResource::Sampler(&sampler),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: source_binding,
+                            resource: wgpu::BindingResource::TextureView(source_view),
+                        },
+                    ],
+                });
+                let view = create_face_view(texture, face, mip);
+                draw_fullscreen(encoder, &view, pipeline, Some((&bind_group, bl.offset)));
+            }
+        };
+
+        // project the source image onto the cube, then fill the mip chain
+        match panorama {
+            super::Panorama::Cube(image) => draw_faces(
+                &mut encoder,
+                &environment,
+                0,
+                0.0,
+                &downsample_pipeline,
+                &cube_bgl,
+                &context.get_image(image).view,
+                3,
+            ),
+            super::Panorama::Equirect(image) => draw_faces(
+                &mut encoder,
+                &environment,
+                0,
+                0.0,
+                &equirect_pipeline,
+                &equirect_bgl,
+                &context.get_image(image).view,
+                2,
+            ),
+        }
+        for mip in 1..environment_mip_count {
+            let parent_view = environment.create_view(&wgpu::TextureViewDescriptor {
+                dimension: Some(wgpu::TextureViewDimension::Cube),
+                base_mip_level: mip - 1,
+                mip_level_count: NonZeroU32::new(1),
+                ..Default::default()
+            });
+            draw_faces(
+                &mut encoder,
+                &environment,
+                mip,
+                0.0,
+                &downsample_pipeline,
+                &cube_bgl,
+                &parent_view,
+                3,
+            );
+        }
+
+        draw_faces(
+            &mut encoder,
+            &irradiance,
+            0,
+            0.0,
+            &irradiance_pipeline,
+            &cube_bgl,
+            &environment_view,
+            3,
+        );
+        for mip in 0..SPECULAR_MIP_COUNT {
+            let roughness = mip as f32 / (SPECULAR_MIP_COUNT - 1) as f32;
+            draw_faces(
+                &mut encoder,
+                &specular,
+                mip,
+                roughness,
+                &specular_pipeline,
+                &cube_bgl,
+                &environment_view,
+                3,
+            );
+        }
+
+        let brdf_lut_view = brdf_lut.create_view(&wgpu::TextureViewDescriptor::default());
+        draw_fullscreen(&mut encoder, &brdf_lut_view, &brdf_pipeline, None);
+
+        queue.submit(Some(encoder.finish()));
+
+        let cube_view_desc = wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::Cube),
+            ..Default::default()
+        };
+        Self {
+            irradiance_view: irradiance.create_view(&cube_view_desc),
+            specular_view: specular.create_view(&cube_view_desc),
+            brdf_lut_view,
+            specular_mip_count: SPECULAR_MIP_COUNT,
+        }
+    }
+}
@@ -0,0 +1,189 @@
+struct Params {
+    face: u32;
+    roughness: f32;
+};
+[[group(0), binding(0)]]
+var<uniform> params: Params;
+
+[[group(0), binding(1)]]
+var sam: sampler;
+[[group(0), binding(2)]]
+var equirect_map: texture_2d<f32>;
+[[group(0), binding(3)]]
+var env_map: texture_cube<f32>;
+
+let PI: f32 = 3.141592653589793;
+// Has to match `ENVIRONMENT_SIZE` on the host side.
+let ENVIRONMENT_SIZE: f32 = 256.0;
+let SPECULAR_SAMPLE_COUNT: u32 = 64u;
+let BRDF_SAMPLE_COUNT: u32 = 256u;
+
+struct Varyings {
+    [[builtin(position)]] clip_pos: vec4<f32>;
+    // X goes right, Y goes down, both in [-1, 1] range
+    [[location(0)]] uv: vec2<f32>;
+};
+
+[[stage(vertex)]]
+fn main_vs([[builtin(vertex_index)]] index: u32) -> Varyings {
+    let pos = vec2<f32>(
+        f32(i32(index & 1u) * 4 - 1),
+        f32(i32(index & 2u) * 2 - 1),
+    );
+    return Varyings(
+        vec4<f32>(pos, 0.0, 1.0),
+        vec2<f32>(pos.x, -pos.y),
+    );
+}
+
+fn face_dir(face: u32, uv: vec2<f32>) -> vec3<f32> {
+    switch (i32(face)) {
+        case 0: { return normalize(vec3<f32>(1.0, -uv.y, -uv.x)); }
+        case 1: { return normalize(vec3<f32>(-1.0, -uv.y, uv.x)); }
+        case 2: { return normalize(vec3<f32>(uv.x, 1.0, uv.y)); }
+        case 3: { return normalize(vec3<f32>(uv.x, -1.0, -uv.y)); }
+        case 4: { return normalize(vec3<f32>(uv.x, -uv.y, 1.0)); }
+        default: { return normalize(vec3<f32>(-uv.x, -uv.y, -1.0)); }
+    }
+}
+
+fn equirect_uv(dir: vec3<f32>) -> vec2<f32> {
+    return vec2<f32>(
+        0.5 + 0.5 * atan2(dir.z, dir.x) / PI,
+        acos(clamp(dir.y, -1.0, 1.0)) / PI,
+    );
+}
+
+// Builds an orthonormal basis with Z looking along the normal.
+fn tangent_to_world(n: vec3<f32>, v: vec3<f32>) -> vec3<f32> {
+    var up = vec3<f32>(0.0, 1.0, 0.0);
+    if (abs(n.y) > 0.999) {
+        up = vec3<f32>(1.0, 0.0, 0.0);
+    }
+    let tangent = normalize(cross(up, n));
+    let bitangent = cross(n, tangent);
+    return v.x * tangent + v.y * bitangent + v.z * n;
+}
+
+fn radical_inverse(index: u32) -> f32 {
+    var bits = (index << 16u) | (index >> 16u);
+    bits = ((bits & 0x55555555u) << 1u) | ((bits & 0xAAAAAAAAu) >> 1u);
+    bits = ((bits & 0x33333333u) << 2u) | ((bits & 0xCCCCCCCCu) >> 2u);
+    bits = ((bits & 0x0F0F0F0Fu) << 4u) | ((bits & 0xF0F0F0F0u) >> 4u);
+    bits = ((bits & 0x00FF00FFu) << 8u) | ((bits & 0xFF00FF00u) >> 8u);
+    return f32(bits) * 2.3283064365386963e-10;
+}
+
+fn hammersley(index: u32, count: u32) -> vec2<f32> {
+    return vec2<f32>(f32(index) / f32(count), radical_inverse(index));
+}
+
+// Returns the half-vector in tangent space.
+fn importance_sample_ggx(xi: vec2<f32>, roughness: f32) -> vec3<f32> {
+    let a = roughness * roughness;
+    let phi = 2.0 * PI * xi.x;
+    let cos_theta = sqrt((1.0 - xi.y) / (1.0 + (a * a - 1.0) * xi.y));
+    let sin_theta = sqrt(1.0 - cos_theta * cos_theta);
+    return vec3<f32>(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta);
+}
+
+fn distribution_ggx(ndoth: f32, roughness: f32) -> f32 {
+    let a2 = roughness * roughness * roughness * roughness;
+    let f = ndoth * ndoth * (a2 - 1.0) + 1.0;
+    return a2 / (PI * f * f);
+}
+
+fn geometry_smith_ibl(ndotv: f32, ndotl: f32, roughness: f32) -> f32 {
+    let k = roughness * roughness / 2.0;
+    let gv = ndotv / (ndotv * (1.0 - k) + k);
+    let gl = ndotl / (ndotl * (1.0 - k) + k);
+    return gv * gl;
+}
+
+[[stage(fragment)]]
+fn equirect_fs(in: Varyings) -> [[location(0)]] vec4<f32> {
+    let dir = face_dir(params.face, in.uv);
+    return textureSampleLevel(equirect_map, sam, equirect_uv(dir), 0.0);
+}
+
+[[stage(fragment)]]
+fn downsample_fs(in: Varyings) -> [[location(0)]] vec4<f32> {
+    let dir = face_dir(params.face, in.uv);
+    return textureSampleLevel(env_map, sam, dir, 0.0);
+}
+
+[[stage(fragment)]]
+fn irradiance_fs(in: Varyings) -> [[location(0)]] vec4<f32> {
+    let n = face_dir(params.face, in.uv);
+    let phi_count = 32u;
+    let theta_count = 8u;
+    var sum = vec3<f32>(0.0);
+    for (var i = 0u; i < phi_count; i = i + 1u) {
+        let phi = 2.0 * PI * (f32(i) + 0.5) / f32(phi_count);
+        for (var j = 0u; j < theta_count; j = j + 1u) {
+            let theta = 0.5 * PI * (f32(j) + 0.5) / f32(theta_count);
+            let local = vec3<f32>(sin(theta) * cos(phi), sin(theta) * sin(phi), cos(theta));
+            // lower mips are already pre-filtered, and converge faster
+            let radiance = textureSampleLevel(env_map, sam, tangent_to_world(n, local), 4.0).xyz;
+            sum = sum + radiance * cos(theta) * sin(theta);
+        }
+    }
+    return vec4<f32>(PI * sum / f32(phi_count * theta_count), 1.0);
+}
+
+[[stage(fragment)]]
+fn specular_fs(in: Varyings) -> [[location(0)]] vec4<f32> {
+    // assume the view direction to be aligned with the normal
+    let n = face_dir(params.face, in.uv);
+    let texel_solid_angle = 4.0 * PI / (6.0 * ENVIRONMENT_SIZE * ENVIRONMENT_SIZE);
+
+    var sum = vec3<f32>(0.0);
+    var weight = 0.0;
+    for (var i = 0u; i < SPECULAR_SAMPLE_COUNT; i = i + 1u) {
+        let xi = hammersley(i, SPECULAR_SAMPLE_COUNT);
+        let h = tangent_to_world(n, importance_sample_ggx(xi, params.roughness));
+        let l = normalize(2.0 * dot(n, h) * h - n);
+        let ndotl = dot(n, l);
+        if (ndotl > 0.0) {
+            // pick the mip level based on the sample footprint
+            let ndoth = max(dot(n, h), 0.0);
+            let pdf = distribution_ggx(ndoth, params.roughness) * 0.25 + 0.0001;
+            let sample_solid_angle = 1.0 / (f32(SPECULAR_SAMPLE_COUNT) * pdf + 0.0001);
+            var lod = 0.0;
+            if (params.roughness > 0.0) {
+                lod = max(0.5 * log2(sample_solid_angle / texel_solid_angle), 0.0);
+            }
+            sum = sum + textureSampleLevel(env_map, sam, l, lod).xyz * ndotl;
+            weight = weight + ndotl;
+        }
+    }
+    return vec4<f32>(sum / max(weight, 0.0001), 1.0);
+}
+
+[[stage(fragment)]]
+fn brdf_fs(in: Varyings) -> [[location(0)]] vec4<f32> {
+    let tc = 0.5 * in.uv + 0.5;
+    let ndotv = max(tc.x, 0.001);
+    let roughness = tc.y;
+    let v = vec3<f32>(sqrt(1.0 - ndotv * ndotv), 0.0, ndotv);
+
+    var scale = 0.0;
+    var bias = 0.0;
+    for (var i = 0u; i < BRDF_SAMPLE_COUNT; i = i + 1u) {
+        let xi = hammersley(i, BRDF_SAMPLE_COUNT);
+        let h = importance_sample_ggx(xi, roughness);
+        let l = normalize(2.0 * dot(v, h) * h - v);
+        let ndotl = max(l.z, 0.0);
+        if (ndotl > 0.0) {
+            let ndoth = max(h.z, 0.0);
+            let vdoth = max(dot(v, h), 0.0);
+            let g = geometry_smith_ibl(ndotv, ndotl, roughness);
+            let g_vis = g * vdoth / (ndoth * ndotv);
+            let fc = pow(1.0 - vdoth, 5.0);
+            scale = scale + (1.0 - fc) * g_vis;
+            bias = bias + fc * g_vis;
+        }
+    }
+    let count = f32(BRDF_SAMPLE_COUNT);
+    return vec4<f32>(scale / count, bias / count, 0.0, 1.0);
+}
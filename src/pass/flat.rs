@@ -18,10 +18,12 @@ struct Locals {
     tex_coords: [f32; 4],
 }
 
-#[derive(Eq, Hash, PartialEq)]
+#[derive(Clone, Copy, Eq, Hash, PartialEq)]
 struct LocalKey {
     uniform_buf_index: usize,
     image: crate::ImageRef,
+    /// The image view is replaced when its target is resized.
+    image_generation: u32,
 }
 
 struct Pipelines {
@@ -31,7 +33,7 @@ struct Pipelines {
 struct Instance {
     camera_distance: f32,
     locals_bl: super::BufferLocation,
-    image: crate::ImageRef,
+    local_key: LocalKey,
 }
 
 pub struct Flat {
@@ -40,8 +42,57 @@ pub struct Flat {
     local_bind_group_layout: wgpu::BindGroupLayout,
     local_bind_groups: fxhash::FxHashMap<LocalKey, wgpu::BindGroup>,
     uniform_pool: super::BufferPool,
-    pipelines: Pipelines,
+    shader_module: wgpu::ShaderModule,
+    pipeline_layout: wgpu::PipelineLayout,
+    pipelines: fxhash::FxHashMap<(super::TargetKey, wgpu::CompareFunction), Pipelines>,
     temp: Vec<Instance>,
+    load_ops: bc::LoadOps,
+}
+
+fn create_pipelines(
+    layout: &wgpu::PipelineLayout,
+    shader_module: &wgpu::ShaderModule,
+    target: super::TargetKey,
+    depth_compare: wgpu::CompareFunction,
+    device: &wgpu::Device,
+) -> Pipelines {
+    let transparent = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("flat-transparent"),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            buffers: &[],
+            module: shader_module,
+            entry_point: "main_vs",
+        },
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleStrip,
+            ..Default::default()
+        },
+        // sprites are occluded by the depth left by previous passes, if any
+        depth_stencil: target.depth_format.map(|format| wgpu::DepthStencilState {
+            format,
+            depth_compare,
+            depth_write_enabled: false,
+            bias: Default::default(),
+            stencil: Default::default(),
+        }),
+        multisample: wgpu::MultisampleState {
+            count: target.sample_count,
+            ..Default::default()
+        },
+        fragment: Some(wgpu::FragmentState {
+            targets: &[wgpu::ColorTargetState {
+                format: target.format,
+                blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                write_mask: wgpu::ColorWrites::all(),
+            }],
+            module: shader_module,
+            entry_point: "main_fs",
+        }),
+        multiview: None,
+    });
+
+    Pipelines { transparent }
 }
 
 impl Flat {
@@ -137,38 +188,18 @@ impl Flat {
             push_constant_ranges: &[],
         });
 
-        let pipelines = {
-            let transparent = d.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                label: Some("flat-transparent"),
-                layout: Some(&pipeline_layout),
-                vertex: wgpu::VertexState {
-                    buffers: &[],
-                    module: &shader_module,
-                    entry_point: "main_vs",
-                },
-                primitive: wgpu::PrimitiveState {
-                    topology: wgpu::PrimitiveTopology::TriangleStrip,
-                    ..Default::default()
-                },
-                depth_stencil: None,
-                multisample: wgpu::MultisampleState {
-                    count: target_info.sample_count,
-                    ..Default::default()
-                },
-                fragment: Some(wgpu::FragmentState {
-                    targets: &[wgpu::ColorTargetState {
-                        format: target_info.format,
-                        blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
-                        write_mask: wgpu::ColorWrites::all(),
-                    }],
-                    module: &shader_module,
-                    entry_point: "main_fs",
-                }),
-                multiview: None,
-            });
-
-            Pipelines { transparent }
-        };
+        let target_key = super::TargetKey::from_info(&target_info);
+        let mut pipelines = fxhash::FxHashMap::default();
+        pipelines.insert(
+            (target_key, wgpu::CompareFunction::LessEqual),
+            create_pipelines(
+                &pipeline_layout,
+                &shader_module,
+                target_key,
+                wgpu::CompareFunction::LessEqual,
+                d,
+            ),
+        );
 
         Self {
             global_uniform_buf,
@@ -176,10 +207,19 @@ impl Flat {
             local_bind_group_layout: local_bgl,
             local_bind_groups: Default::default(),
             uniform_pool: super::BufferPool::uniform("flat locals", d),
+            shader_module,
+            pipeline_layout,
             pipelines,
             temp: Vec::new(),
+            load_ops: bc::LoadOps::default(),
         }
     }
+
+    /// Choose whether the pass clears its target, or draws over
+    /// the contents left by the previous passes.
+    pub fn set_load_ops(&mut self, load_ops: bc::LoadOps) {
+        self.load_ops = load_ops;
+    }
 }
 
 impl bc::Pass for Flat {
@@ -190,16 +230,26 @@ impl bc::Pass for Flat {
         camera: &crate::Camera,
         context: &crate::Context,
     ) {
+        super::assert_mono(camera, "Flat");
         let target = context.get_target(targets[0]);
+        let target_key = super::TargetKey::new(target);
+        let depth_compare = camera.depth_compare();
         let device = context.device();
 
+        let (layout, shader_module) = (&self.pipeline_layout, &self.shader_module);
+        self.pipelines
+            .entry((target_key, depth_compare))
+            .or_insert_with(|| {
+                create_pipelines(layout, shader_module, target_key, depth_compare, device)
+            });
+
         let nodes = scene.bake();
         let cam_node = &nodes[camera.node];
         self.uniform_pool.reset();
         let queue = context.queue();
 
         {
-            let m_proj = camera.projection_matrix(target.aspect());
+            let m_proj = camera.target_projection_matrix(target.size);
             let m_view_inv = cam_node.inverse_matrix();
             let m_final = glam::Mat4::from(m_proj) * glam::Mat4::from(m_view_inv);
             let globals = Globals {
@@ -213,7 +263,14 @@ impl bc::Pass for Flat {
         self.uniform_pool.reset();
         let cam_dir = glam::Quat::from_slice(&cam_node.rot) * -glam::Vec3::Z;
 
-        for (_, (sprite,)) in scene.world.query::<(&bc::Sprite,)>().iter() {
+        for (_, (sprite, visibility)) in scene
+            .world
+            .query::<(&bc::Sprite, Option<&bc::Visibility>)>()
+            .iter()
+        {
+            if !camera.sees(visibility) {
+                continue;
+            }
             let space = &nodes[sprite.node];
             let cam_vector = glam::Vec3::from_slice(&space.pos_scale)
                 - glam::Vec3::from_slice(&cam_node.pos_scale);
@@ -252,8 +309,15 @@ impl bc::Pass for Flat {
             let key = LocalKey {
                 uniform_buf_index: locals_bl.index,
                 image: sprite.image,
+                image_generation: image.generation,
             };
             let binding = self.uniform_pool.binding::<Locals>(locals_bl.index);
+            if !self.local_bind_groups.contains_key(&key) {
+                // drop the bind groups of the replaced image views
+                self.local_bind_groups.retain(|other, _| {
+                    other.uniform_buf_index != key.uniform_buf_index || other.image != key.image
+                });
+            }
             self.local_bind_groups.entry(key).or_insert_with(|| {
                 device.create_bind_group(&wgpu::BindGroupDescriptor {
                     label: Some("flat locals"),
@@ -274,7 +338,7 @@ impl bc::Pass for Flat {
             self.temp.push(Instance {
                 camera_distance,
                 locals_bl,
-                image: sprite.image,
+                local_key: key,
             });
         }
 
@@ -289,28 +353,34 @@ impl bc::Pass for Flat {
                 label: Some("flat"),
                 color_attachments: &[wgpu::RenderPassColorAttachment {
                     view: &target.view,
-                    resolve_target: None,
+                    resolve_target: target.resolve_view.as_ref(),
                     ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(camera.background.into()),
+                        load: self.load_ops.color_op(camera.background),
                         store: true,
                     },
                 }],
-                depth_stencil_attachment: None,
+                depth_stencil_attachment: target.depth.as_ref().map(|depth| {
+                    wgpu::RenderPassDepthStencilAttachment {
+                        view: &depth.view,
+                        depth_ops: Some(wgpu::Operations {
+                            load: self.load_ops.depth_op(camera.far_depth()),
+                            store: true,
+                        }),
+                        stencil_ops: None,
+                    }
+                }),
             });
-            pass.set_pipeline(&self.pipelines.transparent);
+            camera.apply_viewport(&mut pass, target.size);
+            pass.set_pipeline(&self.pipelines[&(target_key, depth_compare)].transparent);
             pass.set_bind_group(0, &self.global_bind_group, &[]);
 
             for inst in self.temp.drain(..) {
-                let key = LocalKey {
-                    uniform_buf_index: inst.locals_bl.index,
-                    image: inst.image,
-                };
-                let local_bg = &self.local_bind_groups[&key];
+                let local_bg = &self.local_bind_groups[&inst.local_key];
                 pass.set_bind_group(1, local_bg, &[inst.locals_bl.offset]);
                 pass.draw(0..4, 0..1);
             }
         }
 
-        queue.submit(Some(encoder.finish()));
+        context.submit(encoder.finish());
     }
 }
@@ -0,0 +1,151 @@
+use bc::ContextDetail as _;
+
+/// Post-processing pass that smooths the aliased edges of an image.
+///
+/// A cheaper alternative to multisampling, reading the source target
+/// and writing the anti-aliased result into the first target.
+/// It works best on colors already in the displayable range,
+/// e.g. after `Tonemap`. Like the latter, it reads the second target
+/// instead of its source, if drawn with one.
+pub struct Fxaa {
+    source: crate::TargetRef,
+    sampler: wgpu::Sampler,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: Option<(wgpu::BindGroup, crate::TargetRef, wgpu::Extent3d)>,
+    shader_module: wgpu::ShaderModule,
+    pipeline_layout: wgpu::PipelineLayout,
+    pipelines: super::FullscreenPipelines,
+}
+
+impl Fxaa {
+    pub fn new(source: crate::TargetRef, context: &crate::Context) -> Self {
+        Self::new_offscreen(source, context.surface_info().unwrap(), context)
+    }
+    pub fn new_offscreen(
+        source: crate::TargetRef,
+        target_info: crate::TargetInfo,
+        context: &crate::Context,
+    ) -> Self {
+        let d = context.device();
+        let shader_module = d.create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label: Some("fxaa"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("fxaa.wgsl").into()),
+        });
+
+        let sampler = d.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("fxaa sampler"),
+            min_filter: wgpu::FilterMode::Linear,
+            mag_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let bind_group_layout = d.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("fxaa"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let pipeline_layout = d.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("fxaa"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let mut pipelines = super::FullscreenPipelines::new("fxaa", "main_fs");
+        pipelines.get(
+            super::TargetKey::from_info(&target_info),
+            &pipeline_layout,
+            &shader_module,
+            d,
+        );
+
+        Self {
+            source,
+            sampler,
+            bind_group_layout,
+            bind_group: None,
+            shader_module,
+            pipeline_layout,
+            pipelines,
+        }
+    }
+}
+
+impl bc::Pass for Fxaa {
+    fn draw(
+        &mut self,
+        targets: &[crate::TargetRef],
+        _scene: &crate::Scene,
+        _camera: &crate::Camera,
+        context: &crate::Context,
+    ) {
+        let target = context.get_target(targets[0]);
+        let source_ref = targets.get(1).copied().unwrap_or(self.source);
+        let source = context.get_target(source_ref);
+        let device = context.device();
+
+        // the source view is re-created when it's resized
+        let reset_bind_group = match self.bind_group {
+            Some((_, tr, size)) => tr != source_ref || size != source.size,
+            None => true,
+        };
+        if reset_bind_group {
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("fxaa"),
+                layout: &self.bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::Sampler(&self.sampler),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::TextureView(source.resolved_view()),
+                    },
+                ],
+            });
+            self.bind_group = Some((bind_group, source_ref, source.size));
+        }
+
+        let pipeline = self.pipelines.get(
+            super::TargetKey::new(target),
+            &self.pipeline_layout,
+            &self.shader_module,
+            device,
+        );
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("fxaa"),
+                color_attachments: &[wgpu::RenderPassColorAttachment {
+                    view: &target.view,
+                    resolve_target: target.resolve_view.as_ref(),
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: true,
+                    },
+                }],
+                depth_stencil_attachment: None,
+            });
+            pass.set_pipeline(pipeline);
+            pass.set_bind_group(0, &self.bind_group.as_ref().unwrap().0, &[]);
+            pass.draw(0..3, 0..1);
+        }
+
+        context.submit(encoder.finish());
+    }
+}
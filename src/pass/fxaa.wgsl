@@ -0,0 +1,136 @@
+[[group(0), binding(0)]]
+var sam: sampler;
+[[group(0), binding(1)]]
+var source: texture_2d<f32>;
+
+struct Varyings {
+    [[builtin(position)]] clip_pos: vec4<f32>;
+    [[location(0)]] uv: vec2<f32>;
+};
+
+[[stage(vertex)]]
+fn main_vs([[builtin(vertex_index)]] index: u32) -> Varyings {
+    let pos = vec2<f32>(
+        f32(i32(index & 1u) * 4 - 1),
+        f32(i32(index & 2u) * 2 - 1),
+    );
+    return Varyings(
+        vec4<f32>(pos, 0.0, 1.0),
+        vec2<f32>(0.5 * pos.x + 0.5, 0.5 - 0.5 * pos.y),
+    );
+}
+
+let EDGE_THRESHOLD_MIN: f32 = 0.0312;
+let EDGE_THRESHOLD_MAX: f32 = 0.125;
+let SUBPIXEL_QUALITY: f32 = 0.75;
+let SEARCH_STEPS: i32 = 10;
+
+// Perceptual luma, with the square root approximating the gamma curve.
+fn luma(color: vec3<f32>) -> f32 {
+    return sqrt(dot(color, vec3<f32>(0.299, 0.587, 0.114)));
+}
+
+fn luma_at(uv: vec2<f32>) -> f32 {
+    return luma(textureSampleLevel(source, sam, uv, 0.0).xyz);
+}
+
+// Simplified FXAA 3.11 by Timothy Lottes.
+[[stage(fragment)]]
+fn main_fs(in: Varyings) -> [[location(0)]] vec4<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(source, 0));
+    let uv = in.uv;
+    let center = textureSampleLevel(source, sam, uv, 0.0);
+
+    let l_c = luma(center.xyz);
+    let l_u = luma_at(uv + vec2<f32>(0.0, -texel.y));
+    let l_d = luma_at(uv + vec2<f32>(0.0, texel.y));
+    let l_l = luma_at(uv + vec2<f32>(-texel.x, 0.0));
+    let l_r = luma_at(uv + vec2<f32>(texel.x, 0.0));
+    let l_min = min(l_c, min(min(l_u, l_d), min(l_l, l_r)));
+    let l_max = max(l_c, max(max(l_u, l_d), max(l_l, l_r)));
+    let range = l_max - l_min;
+    if (range < max(EDGE_THRESHOLD_MIN, l_max * EDGE_THRESHOLD_MAX)) {
+        return center;
+    }
+
+    let l_ul = luma_at(uv - texel);
+    let l_dr = luma_at(uv + texel);
+    let l_ur = luma_at(uv + vec2<f32>(texel.x, -texel.y));
+    let l_dl = luma_at(uv + vec2<f32>(-texel.x, texel.y));
+    let l_ud = l_u + l_d;
+    let l_lr = l_l + l_r;
+    let l_left_corners = l_ul + l_dl;
+    let l_right_corners = l_ur + l_dr;
+    let l_up_corners = l_ul + l_ur;
+    let l_down_corners = l_dl + l_dr;
+
+    // pick the dominant edge direction
+    let edge_h = abs(l_left_corners - 2.0 * l_l) + 2.0 * abs(l_ud - 2.0 * l_c) + abs(l_right_corners - 2.0 * l_r);
+    let edge_v = abs(l_up_corners - 2.0 * l_u) + 2.0 * abs(l_lr - 2.0 * l_c) + abs(l_down_corners - 2.0 * l_d);
+    let is_horizontal = edge_h >= edge_v;
+
+    // find the side of the edge with the steepest gradient
+    let l_neg = select(l_l, l_u, is_horizontal);
+    let l_pos = select(l_r, l_d, is_horizontal);
+    let grad_neg = l_neg - l_c;
+    let grad_pos = l_pos - l_c;
+    let is_neg_steepest = abs(grad_neg) >= abs(grad_pos);
+    let grad_scaled = 0.25 * max(abs(grad_neg), abs(grad_pos));
+    var step_length = select(texel.x, texel.y, is_horizontal);
+    var l_local_avg = 0.5 * (l_pos + l_c);
+    if (is_neg_steepest) {
+        step_length = -step_length;
+        l_local_avg = 0.5 * (l_neg + l_c);
+    }
+
+    // walk along the edge in both directions until its ends
+    var edge_uv = uv;
+    if (is_horizontal) {
+        edge_uv.y = edge_uv.y + 0.5 * step_length;
+    } else {
+        edge_uv.x = edge_uv.x + 0.5 * step_length;
+    }
+    let offset = select(vec2<f32>(0.0, texel.y), vec2<f32>(texel.x, 0.0), is_horizontal);
+    var uv1 = edge_uv - offset;
+    var uv2 = edge_uv + offset;
+    var end1 = luma_at(uv1) - l_local_avg;
+    var end2 = luma_at(uv2) - l_local_avg;
+    var reached1 = abs(end1) >= grad_scaled;
+    var reached2 = abs(end2) >= grad_scaled;
+    for (var i = 1; i < SEARCH_STEPS && !(reached1 && reached2); i = i + 1) {
+        let stride = select(1.0, 2.0, i > 4);
+        if (!reached1) {
+            uv1 = uv1 - stride * offset;
+            end1 = luma_at(uv1) - l_local_avg;
+            reached1 = abs(end1) >= grad_scaled;
+        }
+        if (!reached2) {
+            uv2 = uv2 + stride * offset;
+            end2 = luma_at(uv2) - l_local_avg;
+            reached2 = abs(end2) >= grad_scaled;
+        }
+    }
+
+    let dist1 = select(uv.y - uv1.y, uv.x - uv1.x, is_horizontal);
+    let dist2 = select(uv2.y - uv.y, uv2.x - uv.x, is_horizontal);
+    let is_dir1 = dist1 < dist2;
+    let pixel_offset = 0.5 - min(dist1, dist2) / (dist1 + dist2);
+    // only blend if the luma variation at the closest end is coherent with the center
+    let is_center_smaller = l_c < l_local_avg;
+    let correct_variation = select((end2 < 0.0) != is_center_smaller, (end1 < 0.0) != is_center_smaller, is_dir1);
+    let edge_offset = select(0.0, pixel_offset, correct_variation);
+
+    // sub-pixel aliasing, based on the contrast with the 3x3 neighborhood
+    let l_avg = (2.0 * (l_ud + l_lr) + l_left_corners + l_right_corners) / 12.0;
+    let sub1 = clamp(abs(l_avg - l_c) / range, 0.0, 1.0);
+    let sub2 = (3.0 - 2.0 * sub1) * sub1 * sub1;
+    let final_offset = max(edge_offset, sub2 * sub2 * SUBPIXEL_QUALITY);
+
+    var final_uv = uv;
+    if (is_horizontal) {
+        final_uv.y = final_uv.y + final_offset * step_length;
+    } else {
+        final_uv.x = final_uv.x + final_offset * step_length;
+    }
+    return textureSampleLevel(source, sam, final_uv, 0.0);
+}
@@ -1,14 +1,357 @@
+mod atmosphere;
+mod bloom;
+mod environment;
 mod flat;
+mod fxaa;
+mod multiview;
 mod phong;
 mod real;
+mod reflection;
+mod section;
+mod sky;
 mod solid;
+mod ssao;
+mod tonemap;
 
+pub use atmosphere::{Atmosphere, AtmosphereConfig};
+pub use bloom::{Bloom, BloomConfig};
+pub use environment::Environment;
 pub use flat::Flat;
+pub use fxaa::Fxaa;
 pub use phong::{Ambient, Phong, PhongConfig, Shader};
-pub use real::{Material, Real, RealConfig};
+pub use real::{AlphaMode, Material, Real, RealConfig};
+pub use reflection::Reflection;
+pub use sky::Sky;
 pub use solid::{Solid, SolidConfig};
+pub use ssao::SsaoConfig;
+pub use tonemap::{AutoExposure, Exposure, ToneMapping, Tonemap, TonemapConfig};
 
-use std::mem;
+use fxhash::FxHashMap;
+use std::{mem, num::NonZeroU32, ops::Range};
+
+/// Maximum number of views drawn by a pass, one per eye of a stereo camera.
+const MAX_VIEWS: usize = 2;
+
+/// An image that covers all directions around the viewer.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Panorama {
+    /// Cube map image, such as loaded by `Context::load_cube_image`.
+    Cube(crate::ImageRef),
+    /// Equirectangular image, such as an HDR panorama.
+    Equirect(crate::ImageRef),
+}
+
+/// Which faces of an entity are culled, attached as a component.
+///
+/// Entities without it follow the `cull_back_faces` setting of the pass.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Culling {
+    /// Both sides are drawn, and back faces are shaded with flipped normals.
+    None,
+    Back,
+    Front,
+}
+
+impl Culling {
+    fn from_back_faces(cull_back_faces: bool) -> Self {
+        if cull_back_faces {
+            Self::Back
+        } else {
+            Self::None
+        }
+    }
+
+    fn face(self) -> Option<wgpu::Face> {
+        match self {
+            Self::None => None,
+            Self::Back => Some(wgpu::Face::Back),
+            Self::Front => Some(wgpu::Face::Front),
+        }
+    }
+}
+
+/// Properties of a target that the pipelines drawing into it depend on.
+///
+/// Passes create their pipelines lazily for each of these, so that
+/// the same pass can draw into targets of different kinds.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+struct TargetKey {
+    format: wgpu::TextureFormat,
+    sample_count: u32,
+    depth_format: Option<wgpu::TextureFormat>,
+    /// Number of layers drawn at once, for the layered targets.
+    multiview: Option<NonZeroU32>,
+}
+
+impl TargetKey {
+    fn new(target: &bc::Target) -> Self {
+        assert_eq!(
+            target.size.depth_or_array_layers, 1,
+            "Only the Solid, Phong and Real passes can draw into layered targets"
+        );
+        Self::new_layered(target)
+    }
+
+    /// Key of a target that may have several layers, drawn with multiview.
+    fn new_layered(target: &bc::Target) -> Self {
+        Self {
+            format: target.format,
+            sample_count: target.sample_count,
+            depth_format: target.depth.as_ref().map(|depth| depth.format),
+            multiview: NonZeroU32::new(target.size.depth_or_array_layers).filter(|n| n.get() > 1),
+        }
+    }
+
+    fn from_info(info: &crate::TargetInfo) -> Self {
+        Self {
+            format: info.format,
+            sample_count: info.sample_count,
+            depth_format: info.depth_format,
+            multiview: None,
+        }
+    }
+
+    /// Fill in the format of the depth buffer owned by the pass,
+    /// if the target doesn't have one attached.
+    fn with_default_depth(self, format: wgpu::TextureFormat) -> Self {
+        Self {
+            depth_format: Some(self.depth_format.unwrap_or(format)),
+            ..self
+        }
+    }
+}
+
+/// Check that a pass drawing a single view isn't given a stereo camera.
+fn assert_mono(camera: &bc::Camera, pass_name: &str) {
+    assert!(
+        camera.stereo.is_none(),
+        "{} can't draw stereo cameras, only the Solid, Phong and Real passes can",
+        pass_name
+    );
+}
+
+/// Views of a camera drawn by the passes supporting stereo, see `Camera::views`.
+///
+/// A layered target gets all of them in a single draw with multiview,
+/// otherwise each view is drawn into its viewport, the shaders telling
+/// the views apart by the instance index.
+struct Views {
+    cameras: Vec<bc::Camera>,
+    multiview: bool,
+}
+
+impl Views {
+    fn new(camera: &bc::Camera, target: &bc::Target) -> Self {
+        Self {
+            cameras: camera.views(target.size),
+            multiview: target.size.depth_or_array_layers > 1,
+        }
+    }
+
+    /// Check if all the draws share the viewport of the first view.
+    fn is_shared(&self) -> bool {
+        self.multiview || self.cameras.len() == 1
+    }
+
+    /// View-projection matrices of the views, as seen by the shaders.
+    fn view_proj(&self, view_inv: glam::Mat4, size: wgpu::Extent3d) -> [[[f32; 4]; 4]; MAX_VIEWS] {
+        let mut matrices = [glam::Mat4::IDENTITY.to_cols_array_2d(); MAX_VIEWS];
+        for (matrix, camera) in matrices.iter_mut().zip(self.cameras.iter()) {
+            let m_proj = glam::Mat4::from(camera.target_projection_matrix(size));
+            *matrix = (m_proj * view_inv).to_cols_array_2d();
+        }
+        matrices
+    }
+
+    /// Restrict the pass to the viewport shared by all the draws, if any.
+    fn apply_viewport(&self, pass: &mut wgpu::RenderPass, size: wgpu::Extent3d) {
+        if self.is_shared() {
+            self.cameras[0].apply_viewport(pass, size);
+        }
+    }
+
+    /// Draw something for all the views, given the instances to draw.
+    fn draw<'a>(
+        &self,
+        pass: &mut wgpu::RenderPass<'a>,
+        size: wgpu::Extent3d,
+        mut draw: impl FnMut(&mut wgpu::RenderPass<'a>, Range<u32>),
+    ) {
+        if self.is_shared() {
+            draw(pass, 0..1);
+            return;
+        }
+        for (view, camera) in self.cameras.iter().enumerate() {
+            camera.apply_viewport(pass, size);
+            draw(pass, view as u32..view as u32 + 1);
+        }
+    }
+}
+
+/// Shader of a pass supporting stereo. The multiview variant is only
+/// created for the layered targets, as some backends can't compile it.
+struct StereoShader {
+    label: &'static str,
+    source: &'static str,
+    module: wgpu::ShaderModule,
+    multiview_module: Option<wgpu::ShaderModule>,
+}
+
+impl StereoShader {
+    fn new(label: &'static str, source: &'static str, device: &wgpu::Device) -> Self {
+        debug_assert!(
+            source.contains(multiview::INSTANCE_VIEW),
+            "Shader {} doesn't take the view from the instance index",
+            label
+        );
+        Self {
+            label,
+            source,
+            module: device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+                label: Some(label),
+                source: wgpu::ShaderSource::Wgsl(source.into()),
+            }),
+            multiview_module: None,
+        }
+    }
+
+    /// Create the variant of the shader needed for a target.
+    fn prepare(&mut self, target: TargetKey, device: &wgpu::Device) {
+        if target.multiview.is_none() || self.multiview_module.is_some() {
+            return;
+        }
+        let source = self
+            .source
+            .replace(multiview::INSTANCE_VIEW, multiview::MULTIVIEW_VIEW);
+        self.multiview_module = Some(device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label: Some(self.label),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        }));
+    }
+
+    /// Variant of the shader for a target, created by `prepare`.
+    fn module(&self, target: TargetKey) -> &wgpu::ShaderModule {
+        match target.multiview {
+            Some(_) => self.multiview_module.as_ref().unwrap(),
+            None => &self.module,
+        }
+    }
+}
+
+/// Pipeline drawing a fullscreen triangle without depth,
+/// created for each kind of target on first use.
+struct FullscreenPipelines {
+    label: &'static str,
+    entry_point: &'static str,
+    blend: Option<wgpu::BlendState>,
+    write_mask: wgpu::ColorWrites,
+    pipelines: FxHashMap<TargetKey, wgpu::RenderPipeline>,
+}
+
+impl FullscreenPipelines {
+    fn new(label: &'static str, entry_point: &'static str) -> Self {
+        Self {
+            label,
+            entry_point,
+            blend: None,
+            write_mask: wgpu::ColorWrites::ALL,
+            pipelines: Default::default(),
+        }
+    }
+
+    fn with_blend(self, blend: wgpu::BlendState, write_mask: wgpu::ColorWrites) -> Self {
+        Self {
+            blend: Some(blend),
+            write_mask,
+            ..self
+        }
+    }
+
+    fn get(
+        &mut self,
+        key: TargetKey,
+        layout: &wgpu::PipelineLayout,
+        shader_module: &wgpu::ShaderModule,
+        device: &wgpu::Device,
+    ) -> &wgpu::RenderPipeline {
+        let key = TargetKey {
+            depth_format: None,
+            ..key
+        };
+        let (label, entry_point, blend, write_mask) =
+            (self.label, self.entry_point, self.blend, self.write_mask);
+        self.pipelines.entry(key).or_insert_with(|| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(layout),
+                vertex: wgpu::VertexState {
+                    buffers: &[],
+                    module: shader_module,
+                    entry_point: "main_vs",
+                },
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState {
+                    count: key.sample_count,
+                    ..Default::default()
+                },
+                fragment: Some(wgpu::FragmentState {
+                    targets: &[wgpu::ColorTargetState {
+                        format: key.format,
+                        blend,
+                        write_mask,
+                    }],
+                    module: shader_module,
+                    entry_point,
+                }),
+                multiview: None,
+            })
+        })
+    }
+}
+
+/// Depth buffer owned by a pass, matching the size and sample count of its target.
+/// It's only used if the target doesn't have a depth buffer attached.
+#[derive(Default)]
+struct DepthTexture {
+    view: Option<wgpu::TextureView>,
+    size: wgpu::Extent3d,
+    sample_count: u32,
+    format: Option<wgpu::TextureFormat>,
+}
+
+impl DepthTexture {
+    fn prepare(&mut self, target: &bc::Target, format: wgpu::TextureFormat, device: &wgpu::Device) {
+        if target.depth.is_some()
+            || (self.view.is_some()
+                && self.size == target.size
+                && self.sample_count == target.sample_count
+                && self.format == Some(format))
+        {
+            return;
+        }
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("depth"),
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            size: target.size,
+            sample_count: target.sample_count,
+            mip_level_count: 1,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        });
+        self.view = Some(texture.create_view(&wgpu::TextureViewDescriptor::default()));
+        self.size = target.size;
+        self.sample_count = target.sample_count;
+        self.format = Some(format);
+    }
+
+    fn view<'a>(&'a self, target: &'a bc::Target) -> &'a wgpu::TextureView {
+        match target.depth {
+            Some(ref depth) => &depth.view,
+            None => self.view.as_ref().unwrap(),
+        }
+    }
+}
 
 fn align_up(offset: u32, align: u32) -> u32 {
     (offset + align - 1) & !(align - 1)
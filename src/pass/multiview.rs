@@ -0,0 +1,8 @@
+//! Shader inputs of the view drawn by the passes supporting stereo,
+//! shared with the shader validation tests.
+
+/// Vertex input of the view, selected by the instance index
+/// when each view has its own draw.
+pub(super) const INSTANCE_VIEW: &str = "[[builtin(instance_index)]] view: u32";
+/// Same input in the multiview variant, drawing all the views at once.
+pub(super) const MULTIVIEW_VIEW: &str = "[[builtin(view_index)]] view: i32";
@@ -8,15 +8,16 @@ pub enum Shader {
     Phong { glossiness: u8 },
 }
 
-const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth24Plus;
+const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
 const INTENSITY_THRESHOLD: f32 = 0.1;
 const LIGHT_COUNT: usize = 4;
 
 #[repr(C)]
 #[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
 struct Globals {
-    view_proj: [[f32; 4]; 4],
+    view_proj: [[[f32; 4]; 4]; super::MAX_VIEWS],
     ambient: [f32; 4],
+    clip_planes: [[f32; 4]; bc::MAX_CLIP_PLANES],
 }
 
 #[repr(C)]
@@ -82,7 +83,7 @@ impl Default for PhongConfig {
 }
 
 pub struct Phong {
-    depth_texture: Option<(wgpu::TextureView, wgpu::Extent3d)>,
+    depth_texture: super::DepthTexture,
     global_uniform_buf: wgpu::Buffer,
     light_buf: wgpu::Buffer,
     light_capacity: usize,
@@ -90,9 +91,98 @@ pub struct Phong {
     local_bind_group_layout: wgpu::BindGroupLayout,
     local_bind_groups: FxHashMap<LocalKey, wgpu::BindGroup>,
     uniform_pool: super::BufferPool,
-    pipelines: Pipelines,
+    shader: super::StereoShader,
+    pipeline_layout: wgpu::PipelineLayout,
+    pipelines: FxHashMap<(super::TargetKey, super::Culling, wgpu::CompareFunction), Pipelines>,
+    default_culling: super::Culling,
     ambient: Ambient,
     temp_lights: Vec<(f32, u32)>,
+    section_cap: super::section::SectionCap,
+    load_ops: bc::LoadOps,
+}
+
+fn create_pipelines(
+    layout: &wgpu::PipelineLayout,
+    shader_module: &wgpu::ShaderModule,
+    target: super::TargetKey,
+    culling: super::Culling,
+    depth_compare: wgpu::CompareFunction,
+    device: &wgpu::Device,
+) -> Pipelines {
+    let vertex_buffers = [crate::Position::layout::<0>(), crate::Normal::layout::<1>()];
+    let primitive = wgpu::PrimitiveState {
+        cull_mode: culling.face(),
+        ..Default::default()
+    };
+    let depth_format = target.depth_format.unwrap_or(DEPTH_FORMAT);
+    let ds = Some(wgpu::DepthStencilState {
+        format: depth_format,
+        depth_compare,
+        depth_write_enabled: true,
+        bias: Default::default(),
+        stencil: super::section::stencil_state(depth_format),
+    });
+    let multisample = wgpu::MultisampleState {
+        count: target.sample_count,
+        ..Default::default()
+    };
+
+    Pipelines {
+        flat: device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("phong/flat"),
+            layout: Some(layout),
+            vertex: wgpu::VertexState {
+                buffers: &vertex_buffers,
+                module: shader_module,
+                entry_point: "vs_flat",
+            },
+            primitive,
+            depth_stencil: ds.clone(),
+            multisample,
+            fragment: Some(wgpu::FragmentState {
+                targets: &[target.format.into()],
+                module: shader_module,
+                entry_point: "fs_flat",
+            }),
+            multiview: target.multiview,
+        }),
+        gouraud: device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("phong/gouraud"),
+            layout: Some(layout),
+            vertex: wgpu::VertexState {
+                buffers: &vertex_buffers,
+                module: shader_module,
+                entry_point: "vs_flat",
+            },
+            primitive,
+            depth_stencil: ds.clone(),
+            multisample,
+            fragment: Some(wgpu::FragmentState {
+                targets: &[target.format.into()],
+                module: shader_module,
+                entry_point: "fs_gouraud",
+            }),
+            multiview: target.multiview,
+        }),
+        phong: device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("phong"),
+            layout: Some(layout),
+            vertex: wgpu::VertexState {
+                buffers: &vertex_buffers,
+                module: shader_module,
+                entry_point: "vs_phong",
+            },
+            primitive,
+            depth_stencil: ds.clone(),
+            multisample,
+            fragment: Some(wgpu::FragmentState {
+                targets: &[target.format.into()],
+                module: shader_module,
+                entry_point: "fs_phong",
+            }),
+            multiview: target.multiview,
+        }),
+    }
 }
 
 impl Phong {
@@ -105,10 +195,7 @@ impl Phong {
         context: &crate::Context,
     ) -> Self {
         let d = context.device();
-        let shader_module = d.create_shader_module(&wgpu::ShaderModuleDescriptor {
-            label: Some("phong"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("phong.wgsl").into()),
-        });
+        let shader = super::StereoShader::new("phong", include_str!("phong.wgsl"), d);
 
         let globals_size = mem::size_of::<Globals>() as wgpu::BufferAddress;
         let global_bgl = d.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
@@ -116,7 +203,7 @@ impl Phong {
             entries: &[
                 wgpu::BindGroupLayoutEntry {
                     binding: 0,
-                    visibility: wgpu::ShaderStages::VERTEX,
+                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
                     ty: wgpu::BindingType::Buffer {
                         ty: wgpu::BufferBindingType::Uniform,
                         has_dynamic_offset: false,
@@ -180,93 +267,32 @@ impl Phong {
             }],
         });
 
-        let pipelines = {
-            let pipeline_layout = d.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                label: Some("phong"),
-                bind_group_layouts: &[&global_bgl, &local_bgl],
-                push_constant_ranges: &[],
-            });
-            let vertex_buffers = [crate::Position::layout::<0>(), crate::Normal::layout::<1>()];
-            let primitive = wgpu::PrimitiveState {
-                cull_mode: if config.cull_back_faces {
-                    Some(wgpu::Face::Back)
-                } else {
-                    None
-                },
-                ..Default::default()
-            };
-            let ds = Some(wgpu::DepthStencilState {
-                format: DEPTH_FORMAT,
-                depth_compare: wgpu::CompareFunction::LessEqual,
-                depth_write_enabled: true,
-                bias: Default::default(),
-                stencil: Default::default(),
-            });
-            let multisample = wgpu::MultisampleState {
-                count: target_info.sample_count,
-                ..Default::default()
-            };
-
-            Pipelines {
-                flat: d.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                    label: Some("phong/flat"),
-                    layout: Some(&pipeline_layout),
-                    vertex: wgpu::VertexState {
-                        buffers: &vertex_buffers,
-                        module: &shader_module,
-                        entry_point: "vs_flat",
-                    },
-                    primitive,
-                    depth_stencil: ds.clone(),
-                    multisample,
-                    fragment: Some(wgpu::FragmentState {
-                        targets: &[target_info.format.into()],
-                        module: &shader_module,
-                        entry_point: "fs_flat",
-                    }),
-                    multiview: None,
-                }),
-                gouraud: d.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                    label: Some("phong/gouraud"),
-                    layout: Some(&pipeline_layout),
-                    vertex: wgpu::VertexState {
-                        buffers: &vertex_buffers,
-                        module: &shader_module,
-                        entry_point: "vs_flat",
-                    },
-                    primitive,
-                    depth_stencil: ds.clone(),
-                    multisample,
-                    fragment: Some(wgpu::FragmentState {
-                        targets: &[target_info.format.into()],
-                        module: &shader_module,
-                        entry_point: "fs_gouraud",
-                    }),
-                    multiview: None,
-                }),
-                phong: d.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                    label: Some("phong"),
-                    layout: Some(&pipeline_layout),
-                    vertex: wgpu::VertexState {
-                        buffers: &vertex_buffers,
-                        module: &shader_module,
-                        entry_point: "vs_phong",
-                    },
-                    primitive,
-                    depth_stencil: ds.clone(),
-                    multisample,
-                    fragment: Some(wgpu::FragmentState {
-                        targets: &[target_info.format.into()],
-                        module: &shader_module,
-                        entry_point: "fs_phong",
-                    }),
-                    multiview: None,
-                }),
-            }
-        };
+        let pipeline_layout = d.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("phong"),
+            bind_group_layouts: &[&global_bgl, &local_bgl],
+            push_constant_ranges: &[],
+        });
+        let default_culling = super::Culling::from_back_faces(config.cull_back_faces);
+        let target_key = super::TargetKey::from_info(&target_info).with_default_depth(DEPTH_FORMAT);
+        let mut pipelines = FxHashMap::default();
+        pipelines.insert(
+            (
+                target_key,
+                default_culling,
+                wgpu::CompareFunction::LessEqual,
+            ),
+            create_pipelines(
+                &pipeline_layout,
+                &shader.module,
+                target_key,
+                default_culling,
+                wgpu::CompareFunction::LessEqual,
+                d,
+            ),
+        );
 
         Self {
-            depth_texture: None,
+            depth_texture: Default::default(),
             global_uniform_buf,
             light_capacity: config.max_lights,
             light_buf,
@@ -274,11 +300,22 @@ impl Phong {
             local_bind_group_layout: local_bgl,
             local_bind_groups: Default::default(),
             uniform_pool: super::BufferPool::uniform("phong locals", d),
+            shader,
+            pipeline_layout,
             pipelines,
+            default_culling,
             ambient: config.ambient,
             temp_lights: Vec::new(),
+            section_cap: super::section::SectionCap::new(d),
+            load_ops: bc::LoadOps::default(),
         }
     }
+
+    /// Choose whether the pass clears its target, or draws over
+    /// the contents left by the previous passes.
+    pub fn set_load_ops(&mut self, load_ops: bc::LoadOps) {
+        self.load_ops = load_ops;
+    }
 }
 
 impl bc::Pass for Phong {
@@ -290,43 +327,33 @@ impl bc::Pass for Phong {
         context: &crate::Context,
     ) {
         let target = context.get_target(targets[0]);
+        let target_key = super::TargetKey::new_layered(target)
+            .with_default_depth(super::section::depth_format(camera, DEPTH_FORMAT));
+        let depth_format = target_key.depth_format.unwrap();
+        let cap_color = super::section::cap_color(camera, depth_format);
+        let depth_compare = camera.depth_compare();
         let device = context.device();
 
-        let reset_depth = match self.depth_texture {
-            Some((_, size)) => size != target.size,
-            None => true,
-        };
-        if reset_depth {
-            let texture = device.create_texture(&wgpu::TextureDescriptor {
-                label: Some("depth"),
-                dimension: wgpu::TextureDimension::D2,
-                format: DEPTH_FORMAT,
-                size: target.size,
-                sample_count: 1,
-                mip_level_count: 1,
-                usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            });
-            let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
-            self.depth_texture = Some((view, target.size));
-        }
+        self.depth_texture.prepare(target, depth_format, device);
+        self.shader.prepare(target_key, device);
 
+        let views = super::Views::new(camera, target);
         let nodes = scene.bake();
         self.uniform_pool.reset();
         let queue = context.queue();
 
         {
-            let m_proj = camera.projection_matrix(target.aspect());
             let m_view_inv = nodes[camera.node].inverse_matrix();
-            let m_final = glam::Mat4::from(m_proj) * glam::Mat4::from(m_view_inv);
             let ambient = self.ambient.color.into_vec4();
             let globals = Globals {
-                view_proj: m_final.to_cols_array_2d(),
+                view_proj: views.view_proj(m_view_inv.into(), target.size),
                 ambient: [
                     ambient[0] * self.ambient.intensity,
                     ambient[1] * self.ambient.intensity,
                     ambient[2] * self.ambient.intensity,
                     0.0,
                 ],
+                clip_planes: camera.clip_plane_array(),
             };
             queue.write_buffer(&self.global_uniform_buf, 0, bytemuck::bytes_of(&globals));
         }
@@ -356,15 +383,48 @@ impl bc::Pass for Phong {
             bytemuck::cast_slice(&lights[..light_count]),
         );
 
-        // pre-create the bind groups so that we don't need to do it on the fly
-        let local_bgl = &self.local_bind_group_layout;
-        let entity_count = scene
+        // pre-create the pipelines and bind groups so that we don't need to do it on the fly
+        let mut entity_count = 0;
+        for (_, (_, _, _, culling, visibility)) in scene
             .world
-            .query::<(&bc::Entity, &bc::Color, &Shader)>()
+            .query::<(
+                &bc::Entity,
+                &bc::Color,
+                &Shader,
+                Option<&super::Culling>,
+                Option<&bc::Visibility>,
+            )>()
             .with::<bc::Vertex<crate::Position>>()
             .with::<bc::Vertex<crate::Normal>>()
             .iter()
-            .count();
+        {
+            if !camera.sees(visibility) {
+                continue;
+            }
+            let culling = super::section::culling(
+                cap_color,
+                culling.cloned().unwrap_or(self.default_culling),
+            );
+            let (layout, shader_module) = (&self.pipeline_layout, self.shader.module(target_key));
+            self.pipelines
+                .entry((target_key, culling, depth_compare))
+                .or_insert_with(|| {
+                    create_pipelines(
+                        layout,
+                        shader_module,
+                        target_key,
+                        culling,
+                        depth_compare,
+                        device,
+                    )
+                });
+            entity_count += 1;
+        }
+        if cap_color.is_some() {
+            self.section_cap.prepare(target_key, [None; 2], device);
+        }
+
+        let local_bgl = &self.local_bind_group_layout;
         let uniform_pool_size = self
             .uniform_pool
             .prepare_for_count::<Locals>(entity_count, device);
@@ -391,31 +451,42 @@ impl bc::Pass for Phong {
                 label: Some("phong"),
                 color_attachments: &[wgpu::RenderPassColorAttachment {
                     view: &target.view,
-                    resolve_target: None,
+                    resolve_target: target.resolve_view.as_ref(),
                     ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(camera.background.into()),
+                        load: self.load_ops.color_op(camera.background),
                         store: true,
                     },
                 }],
                 depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                    view: &self.depth_texture.as_ref().unwrap().0,
+                    view: self.depth_texture.view(target),
                     depth_ops: Some(wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(1.0),
+                        load: self.load_ops.depth_op(camera.far_depth()),
                         store: true,
                     }),
-                    stencil_ops: None,
+                    stencil_ops: super::section::stencil_ops(depth_format),
                 }),
             });
+            views.apply_viewport(&mut pass, target.size);
+            pass.set_stencil_reference(super::section::INSIDE);
 
             pass.set_bind_group(0, &self.global_bind_group, &[]);
 
-            for (_, (entity, &color, &shader)) in scene
+            for (_, (entity, &color, &shader, culling, visibility)) in scene
                 .world
-                .query::<(&bc::Entity, &bc::Color, &Shader)>()
+                .query::<(
+                    &bc::Entity,
+                    &bc::Color,
+                    &Shader,
+                    Option<&super::Culling>,
+                    Option<&bc::Visibility>,
+                )>()
                 .with::<bc::Vertex<crate::Position>>()
                 .with::<bc::Vertex<crate::Normal>>()
                 .iter()
             {
+                if !camera.sees(visibility) {
+                    continue;
+                }
                 let space = &nodes[entity.node];
                 let mesh = context.get_mesh(entity.mesh);
                 let entity_radius = mesh.bound_radius * space.pos_scale[3];
@@ -449,16 +520,21 @@ impl bc::Pass for Phong {
                 }
 
                 //TODO: check for texture coordinates
+                let culling = super::section::culling(
+                    cap_color,
+                    culling.cloned().unwrap_or(self.default_culling),
+                );
+                let pipelines = &self.pipelines[&(target_key, culling, depth_compare)];
                 pass.set_pipeline(match shader {
-                    Shader::Gouraud { flat: true } => &self.pipelines.flat,
-                    Shader::Gouraud { flat: false } => &self.pipelines.gouraud,
-                    Shader::Phong { .. } => &self.pipelines.phong,
+                    Shader::Gouraud { flat: true } => &pipelines.flat,
+                    Shader::Gouraud { flat: false } => &pipelines.gouraud,
+                    Shader::Phong { .. } => &pipelines.phong,
                 });
 
                 let locals = Locals {
                     pos_scale: space.pos_scale,
                     rot: space.rot,
-                    color: color.into_vec4_gamma(),
+                    color: color.into_vec4_linear(),
                     lights: light_indices,
                     glossiness: match shader {
                         Shader::Phong { glossiness } => glossiness as f32,
@@ -479,13 +555,24 @@ impl bc::Pass for Phong {
 
                 if let Some(ref is) = mesh.index_stream {
                     pass.set_index_buffer(mesh.buffer.slice(is.offset..), is.format);
-                    pass.draw_indexed(0..is.count, 0, 0..1);
+                    views.draw(&mut pass, target.size, |pass, instances| {
+                        pass.draw_indexed(0..is.count, 0, instances)
+                    });
                 } else {
-                    pass.draw(0..mesh.vertex_count, 0..1);
+                    views.draw(&mut pass, target.size, |pass, instances| {
+                        pass.draw(0..mesh.vertex_count, instances)
+                    });
                 }
             }
+
+            if let Some(color) = cap_color {
+                let section_cap = &self.section_cap;
+                views.draw(&mut pass, target.size, |pass, _| {
+                    section_cap.draw(pass, target_key, [None; 2], color)
+                });
+            }
         }
 
-        queue.submit(Some(encoder.finish()));
+        context.submit(encoder.finish());
     }
 }
@@ -4,8 +4,9 @@ struct Vertex {
 };
 
 struct Globals {
-    view_proj: mat4x4<f32>;
+    view_proj: array<mat4x4<f32>, 2>;
     ambient: vec4<f32>;
+    clip_planes: array<vec4<f32>, 4>;
 };
 [[group(0), binding(0)]]
 var<uniform> globals: Globals;
@@ -35,6 +36,17 @@ fn qrot(q: vec4<f32>, v: vec3<f32>) -> vec3<f32> {
     return v + 2.0*cross(q.xyz, cross(q.xyz,v) + q.w*v);
 }
 
+fn is_clipped(world: vec3<f32>) -> bool {
+    let p = vec4<f32>(world, 1.0);
+    let distances = vec4<f32>(
+        dot(globals.clip_planes[0], p),
+        dot(globals.clip_planes[1], p),
+        dot(globals.clip_planes[2], p),
+        dot(globals.clip_planes[3], p),
+    );
+    return any(distances < vec4<f32>(0.0));
+}
+
 struct PhongVaryings {
     [[builtin(position)]] position: vec4<f32>;
     [[location(0)]] world: vec3<f32>;
@@ -53,12 +65,12 @@ fn compute_half(world: vec3<f32>, normal: vec3<f32>, index: u32) -> vec3<f32> {
 }
 
 [[stage(vertex)]]
-fn vs_phong(in: Vertex) -> PhongVaryings {
+fn vs_phong(in: Vertex, [[builtin(instance_index)]] view: u32) -> PhongVaryings {
     let world = locals.pos_scale.w * qrot(locals.rot, in.pos) + locals.pos_scale.xyz;
     let normal = qrot(locals.rot, normalize(in.normal));
 
     var out: PhongVaryings;
-    out.position = globals.view_proj * vec4<f32>(world, 1.0);
+    out.position = globals.view_proj[view] * vec4<f32>(world, 1.0);
     out.world = world;
     out.normal = normal;
     out.color = globals.ambient.xyz;
@@ -94,12 +106,31 @@ fn evaluate(world: vec3<f32>, normal: vec3<f32>, half_vec: vec3<f32>, index: u32
     return ev;
 }
 
+fn face_half(in_half: vec3<f32>, world: vec3<f32>, normal: vec3<f32>, index: u32, front_facing: bool) -> vec3<f32> {
+    if (front_facing) {
+        return in_half;
+    }
+    return compute_half(world, normal, index);
+}
+
 [[stage(fragment)]]
-fn fs_phong(in: PhongVaryings) -> [[location(0)]] vec4<f32> {
-    let eval0 = evaluate(in.world, in.normal, in.half_vec0, locals.lights.x);
-    let eval1 = evaluate(in.world, in.normal, in.half_vec1, locals.lights.y);
-    let eval2 = evaluate(in.world, in.normal, in.half_vec2, locals.lights.z);
-    let eval3 = evaluate(in.world, in.normal, in.half_vec3, locals.lights.w);
+fn fs_phong(in: PhongVaryings, [[builtin(front_facing)]] front_facing: bool) -> [[location(0)]] vec4<f32> {
+    if (is_clipped(in.world)) {
+        discard;
+    }
+    // back faces are shaded with the flipped normal
+    var normal = in.normal;
+    if (!front_facing) {
+        normal = -normal;
+    }
+    let half_vec0 = face_half(in.half_vec0, in.world, normal, locals.lights.x, front_facing);
+    let half_vec1 = face_half(in.half_vec1, in.world, normal, locals.lights.y, front_facing);
+    let half_vec2 = face_half(in.half_vec2, in.world, normal, locals.lights.z, front_facing);
+    let half_vec3 = face_half(in.half_vec3, in.world, normal, locals.lights.w, front_facing);
+    let eval0 = evaluate(in.world, normal, half_vec0, locals.lights.x);
+    let eval1 = evaluate(in.world, normal, half_vec1, locals.lights.y);
+    let eval2 = evaluate(in.world, normal, half_vec2, locals.lights.z);
+    let eval3 = evaluate(in.world, normal, half_vec3, locals.lights.w);
     let total = Evaluation(
         in.color + eval0.diffuse + eval1.diffuse + eval2.diffuse + eval3.diffuse,
         eval0.specular + eval1.specular + eval2.specular + eval3.specular,
@@ -117,35 +148,59 @@ fn evaluate_flat(world: vec3<f32>, normal: vec3<f32>, index: u32) -> vec3<f32> {
     return kd * light.color_intensity.xyz;
 }
 
+fn evaluate_all_flat(world: vec3<f32>, normal: vec3<f32>) -> vec3<f32> {
+    return globals.ambient.xyz +
+        evaluate_flat(world, normal, locals.lights.x) +
+        evaluate_flat(world, normal, locals.lights.y) +
+        evaluate_flat(world, normal, locals.lights.z) +
+        evaluate_flat(world, normal, locals.lights.w);
+}
+
 struct FlatVaryings {
     [[builtin(position)]] position: vec4<f32>;
     [[location(0), interpolate(flat)]] flat_color: vec3<f32>;
     [[location(1)]] color: vec3<f32>;
+    [[location(2), interpolate(flat)]] flat_back_color: vec3<f32>;
+    [[location(3)]] back_color: vec3<f32>;
+    [[location(4)]] world: vec3<f32>;
 };
 
 [[stage(vertex)]]
-fn vs_flat(in: Vertex) -> FlatVaryings {
+fn vs_flat(in: Vertex, [[builtin(instance_index)]] view: u32) -> FlatVaryings {
     let world = locals.pos_scale.w * qrot(locals.rot, in.pos) + locals.pos_scale.xyz;
     let normal = qrot(locals.rot, normalize(in.normal));
-    let diffuse = globals.ambient.xyz +
-        evaluate_flat(world, normal, locals.lights.x) +
-        evaluate_flat(world, normal, locals.lights.y) +
-        evaluate_flat(world, normal, locals.lights.z) +
-        evaluate_flat(world, normal, locals.lights.w);
+    // the facing is only known in the fragment stage, so both sides are lit
+    let diffuse = evaluate_all_flat(world, normal);
+    let back_diffuse = evaluate_all_flat(world, -normal);
 
     var out: FlatVaryings;
-    out.position = globals.view_proj * vec4<f32>(world, 1.0);
+    out.position = globals.view_proj[view] * vec4<f32>(world, 1.0);
     out.flat_color = diffuse * locals.color.xyz;
     out.color = diffuse * locals.color.xyz;
+    out.flat_back_color = back_diffuse * locals.color.xyz;
+    out.back_color = back_diffuse * locals.color.xyz;
+    out.world = world;
     return out;
 }
 
 [[stage(fragment)]]
-fn fs_flat(in: FlatVaryings) -> [[location(0)]] vec4<f32> {
-    return vec4<f32>(in.flat_color, 0.0);
+fn fs_flat(in: FlatVaryings, [[builtin(front_facing)]] front_facing: bool) -> [[location(0)]] vec4<f32> {
+    if (is_clipped(in.world)) {
+        discard;
+    }
+    if (front_facing) {
+        return vec4<f32>(in.flat_color, 0.0);
+    }
+    return vec4<f32>(in.flat_back_color, 0.0);
 }
 
 [[stage(fragment)]]
-fn fs_gouraud(in: FlatVaryings) -> [[location(0)]] vec4<f32> {
-    return vec4<f32>(in.color, 0.0);
+fn fs_gouraud(in: FlatVaryings, [[builtin(front_facing)]] front_facing: bool) -> [[location(0)]] vec4<f32> {
+    if (is_clipped(in.world)) {
+        discard;
+    }
+    if (front_facing) {
+        return vec4<f32>(in.color, 0.0);
+    }
+    return vec4<f32>(in.back_color, 0.0);
 }
@@ -1,38 +1,79 @@
 use bc::ContextDetail as _;
 use fxhash::FxHashMap;
-use std::mem;
+use std::{iter, mem};
 use wgpu::util::DeviceExt as _;
 
+/// How the alpha of the base color is interpreted.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AlphaMode {
+    /// Alpha is ignored, the surface is fully opaque.
+    Opaque,
+    /// Fragments with alpha below the cutoff are discarded.
+    Mask { cutoff: f32 },
+    /// The surface is blended over whatever is behind it.
+    /// Such objects are drawn after the opaque ones, from back to front.
+    Blend,
+}
+
 #[derive(Clone, Copy, Debug)]
 pub struct Material {
+    pub alpha_mode: AlphaMode,
     pub base_color_map: Option<crate::ImageRef>,
     pub emissive_color: crate::Color,
+    pub emissive_map: Option<crate::ImageRef>,
     pub metallic_factor: f32,
     pub roughness_factor: f32,
+    /// Linear texture with roughness in the green channel,
+    /// and metalness in the blue channel.
+    pub metallic_roughness_map: Option<crate::ImageRef>,
     pub normal_scale: f32,
     pub occlusion_strength: f32,
+    /// Linear texture with occlusion in the red channel.
+    pub occlusion_map: Option<crate::ImageRef>,
 }
 
 impl Default for Material {
     fn default() -> Self {
         Self {
+            alpha_mode: AlphaMode::Opaque,
             base_color_map: None,
             emissive_color: crate::Color(0),
+            emissive_map: None,
             metallic_factor: 1.0,
             roughness_factor: 0.0,
+            metallic_roughness_map: None,
             normal_scale: 1.0,
             occlusion_strength: 1.0,
+            occlusion_map: None,
+        }
+    }
+}
+
+impl Material {
+    fn maps(&self) -> MaterialMaps {
+        MaterialMaps {
+            base_color: self.base_color_map,
+            metallic_roughness: self.metallic_roughness_map,
+            occlusion: self.occlusion_map,
+            emissive: self.emissive_map,
         }
     }
 }
 
-const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth24Plus;
+const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
 
 #[repr(C)]
 #[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
 struct Globals {
-    view_proj: [[f32; 4]; 4],
+    view_proj: [[[f32; 4]; 4]; super::MAX_VIEWS],
+    // both eyes of a stereo camera are lit as seen from their middle point
     camera_pos: [f32; 4],
+    // RGB is the ambient color, A is the environment mip count
+    ambient: [f32; 4],
+    inv_target_size: [f32; 2],
+    environment_intensity: f32,
+    pad: f32,
+    clip_planes: [[f32; 4]; bc::MAX_CLIP_PLANES],
 }
 
 #[repr(C)]
@@ -53,53 +94,371 @@ struct Locals {
     metallic_roughness_values: [f32; 2],
     normal_scale: f32,
     occlusion_strength: f32,
+    alpha_cutoff: f32,
+    entity_id: u32,
+    reflectivity: f32,
+    pad: f32,
+}
+
+#[derive(Clone, Copy, Eq, Hash, PartialEq)]
+struct MaterialMaps {
+    base_color: Option<crate::ImageRef>,
+    metallic_roughness: Option<crate::ImageRef>,
+    occlusion: Option<crate::ImageRef>,
+    emissive: Option<crate::ImageRef>,
 }
 
-#[derive(Eq, Hash, PartialEq)]
+#[derive(Clone, Copy, Eq, Hash, PartialEq)]
 struct LocalKey {
     uniform_buf_index: usize,
-    base_color_map: Option<crate::ImageRef>,
+    maps: MaterialMaps,
+    /// Generations of the map images, which views are replaced
+    /// when their targets are resized.
+    generations: [u32; 4],
 }
 
 #[derive(Debug)]
 pub struct RealConfig {
     pub cull_back_faces: bool,
+    /// Constant ambient lighting, replaced by the image-based lighting
+    /// if an environment is set.
+    pub ambient: super::Ambient,
+    /// Scale of the image-based lighting from the environment.
+    pub environment_intensity: f32,
     pub max_lights: usize,
+    /// Screen-space ambient occlusion, computed from a depth and normal prepass.
+    pub ambient_occlusion: Option<super::SsaoConfig>,
+    /// Maximum number of entities with a `Reflection` drawn per frame.
+    pub max_reflections: usize,
 }
 
 impl Default for RealConfig {
     fn default() -> Self {
         Self {
             cull_back_faces: true,
+            ambient: super::Ambient::default(),
+            environment_intensity: 1.0,
             max_lights: 16,
+            ambient_occlusion: None,
+            max_reflections: 4,
         }
     }
 }
 
 struct Pipelines {
     main: wgpu::RenderPipeline,
+    blend: wgpu::RenderPipeline,
+    prepass: wgpu::RenderPipeline,
+}
+
+/// Kinds of all the targets drawn into at once:
+/// the main color, followed by the formats of the extra outputs.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+struct TargetsKey {
+    main: super::TargetKey,
+    extra: [Option<wgpu::TextureFormat>; 2],
+}
+
+impl TargetsKey {
+    /// Key of the main target and the extra ones, given with their sample counts.
+    /// All of them are drawn in one render pass, so they need the same sample count,
+    /// and the integer IDs can't be multisampled at all.
+    fn new(
+        main: super::TargetKey,
+        extra_targets: impl Iterator<Item = (wgpu::TextureFormat, u32)>,
+    ) -> Self {
+        let mut extra = [None; 2];
+        for (i, (format, sample_count)) in extra_targets.enumerate() {
+            assert_eq!(
+                sample_count, main.sample_count,
+                "Real needs the same sample count in all its targets"
+            );
+            *extra
+                .get_mut(i)
+                .expect("Real can't draw into more than 3 targets") = Some(format);
+        }
+        if extra[1] == Some(wgpu::TextureFormat::R32Uint) {
+            assert_eq!(
+                main.sample_count, 1,
+                "Real can't write entity IDs into multisampled targets, \
+                the context needs a sample count of 1"
+            );
+        }
+        Self { main, extra }
+    }
+}
+
+/// Everything the pipelines of an instance depend on.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+struct PipelineKey {
+    targets: TargetsKey,
+    culling: super::Culling,
+    /// Mirrored views flip the winding of the triangles.
+    front_face: wgpu::FrontFace,
+    depth_compare: wgpu::CompareFunction,
 }
 
 struct Instance {
+    camera_distance: f32,
+    culling: super::Culling,
     mesh: crate::MeshRef,
     locals_bl: super::BufferLocation,
-    base_color_map: Option<crate::ImageRef>,
+    local_key: LocalKey,
+    reflection: Option<usize>,
+}
+
+/// Reflection drawn in the current frame.
+struct ReflectionView {
+    plane: glam::Vec4,
+    resolution_scale: f32,
 }
 
 /// Realistic renderer.
 /// Follows Disney PBR.
+///
+/// Can draw into targets of any format and sample count,
+/// creating the pipelines for each kind on first use.
 pub struct Real {
-    depth_texture: Option<(wgpu::TextureView, wgpu::Extent3d)>,
+    depth_texture: super::DepthTexture,
     global_uniform_buf: wgpu::Buffer,
+    /// Offset between the globals of the camera and of each reflection.
+    globals_stride: wgpu::BufferAddress,
     light_buf: wgpu::Buffer,
     light_capacity: usize,
+    global_bind_group_layout: wgpu::BindGroupLayout,
     global_bind_group: wgpu::BindGroup,
     local_bind_group_layout: wgpu::BindGroupLayout,
     local_bind_groups: FxHashMap<LocalKey, wgpu::BindGroup>,
     uniform_pool: super::BufferPool,
-    pipelines: Pipelines,
+    shader: super::StereoShader,
+    pipeline_layout: wgpu::PipelineLayout,
+    prepass_pipeline_layout: wgpu::PipelineLayout,
+    pipelines: FxHashMap<PipelineKey, Pipelines>,
+    default_culling: super::Culling,
+    ssao: Option<super::ssao::Ssao>,
+    ssao_bind_group_layout: wgpu::BindGroupLayout,
+    ssao_bind_group: wgpu::BindGroup,
+    blank_ssao_bind_group: wgpu::BindGroup,
+    reflection_bind_group_layout: wgpu::BindGroupLayout,
+    blank_reflection_bind_group: wgpu::BindGroup,
+    reflection_capacity: usize,
+    reflection_targets: Vec<super::reflection::ReflectionTarget>,
+    reflection_views: Vec<ReflectionView>,
+    section_cap: super::section::SectionCap,
+    sampler: wgpu::Sampler,
     blank_color_view: wgpu::TextureView,
+    blank_cube_view: wgpu::TextureView,
+    ambient: super::Ambient,
+    environment_intensity: f32,
+    environment_mip_count: u32,
     instances: Vec<Instance>,
+    blended_instances: Vec<Instance>,
+    load_ops: bc::LoadOps,
+}
+
+struct GlobalResources<'a> {
+    uniform_buf: &'a wgpu::Buffer,
+    light_buf: &'a wgpu::Buffer,
+    sampler: &'a wgpu::Sampler,
+    irradiance_view: &'a wgpu::TextureView,
+    specular_view: &'a wgpu::TextureView,
+    brdf_lut_view: &'a wgpu::TextureView,
+}
+
+fn create_global_bind_group(
+    layout: &wgpu::BindGroupLayout,
+    resources: GlobalResources,
+    device: &wgpu::Device,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("real globals"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer: resources.uniform_buf,
+                    offset: 0,
+                    size: wgpu::BufferSize::new(mem::size_of::<Globals>() as _),
+                }),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: resources.light_buf.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: wgpu::BindingResource::Sampler(resources.sampler),
+            },
+            wgpu::BindGroupEntry {
+                binding: 3,
+                resource: wgpu::BindingResource::TextureView(resources.irradiance_view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 4,
+                resource: wgpu::BindingResource::TextureView(resources.specular_view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 5,
+                resource: wgpu::BindingResource::TextureView(resources.brdf_lut_view),
+            },
+        ],
+    })
+}
+
+fn create_ssao_bind_group(
+    layout: &wgpu::BindGroupLayout,
+    view: &wgpu::TextureView,
+    device: &wgpu::Device,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("real ssao"),
+        layout,
+        entries: &[wgpu::BindGroupEntry {
+            binding: 0,
+            resource: wgpu::BindingResource::TextureView(view),
+        }],
+    })
+}
+
+fn create_pipelines(
+    layout: &wgpu::PipelineLayout,
+    prepass_layout: &wgpu::PipelineLayout,
+    shader: &super::StereoShader,
+    key: &PipelineKey,
+    device: &wgpu::Device,
+) -> Pipelines {
+    let targets = &key.targets;
+    let depth_compare = key.depth_compare;
+    let primitive = wgpu::PrimitiveState {
+        front_face: key.front_face,
+        cull_mode: key.culling.face(),
+        ..Default::default()
+    };
+    let multisample = wgpu::MultisampleState {
+        count: targets.main.sample_count,
+        ..Default::default()
+    };
+
+    let vertex_buffers = [
+        crate::Position::layout::<0>(),
+        crate::TexCoords::layout::<1>(),
+        crate::Normal::layout::<2>(),
+    ];
+
+    // extra targets get the normals and the entity IDs
+    let entry_point = match targets.extra {
+        [None, _] => "main_fs",
+        [Some(_), None] => "main_normal_fs",
+        [Some(_), Some(_)] => "main_normal_id_fs",
+    };
+
+    let depth_format = targets.main.depth_format.unwrap_or(DEPTH_FORMAT);
+    let shader_module = shader.module(targets.main);
+    // only the opaque objects mark the stencil for the section caps
+    let create_pipeline = |label, blend, depth_write_enabled| {
+        let color_targets = iter::once(targets.main.format)
+            .chain(targets.extra.iter().flatten().cloned())
+            .enumerate()
+            .map(|(i, format)| wgpu::ColorTargetState {
+                format,
+                blend: if i == 0 { blend } else { None },
+                write_mask: wgpu::ColorWrites::default(),
+            })
+            .collect::<Vec<_>>();
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(label),
+            layout: Some(layout),
+            vertex: wgpu::VertexState {
+                buffers: &vertex_buffers,
+                module: shader_module,
+                entry_point: "main_vs",
+            },
+            primitive,
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: depth_format,
+                depth_compare,
+                depth_write_enabled,
+                bias: Default::default(),
+                stencil: if depth_write_enabled {
+                    super::section::stencil_state(depth_format)
+                } else {
+                    wgpu::StencilState::default()
+                },
+            }),
+            multisample,
+            fragment: Some(wgpu::FragmentState {
+                targets: &color_targets,
+                module: shader_module,
+                entry_point,
+            }),
+            multiview: targets.main.multiview,
+        })
+    };
+
+    // the occlusion is only computed for mono cameras
+    let prepass = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("real prepass"),
+        layout: Some(prepass_layout),
+        vertex: wgpu::VertexState {
+            buffers: &vertex_buffers,
+            module: &shader.module,
+            entry_point: "main_vs",
+        },
+        primitive,
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: super::ssao::DEPTH_FORMAT,
+            depth_compare,
+            depth_write_enabled: true,
+            bias: Default::default(),
+            stencil: Default::default(),
+        }),
+        multisample: wgpu::MultisampleState::default(),
+        fragment: Some(wgpu::FragmentState {
+            targets: &[super::ssao::NORMAL_FORMAT.into()],
+            module: &shader.module,
+            entry_point: "prepass_fs",
+        }),
+        multiview: None,
+    });
+
+    Pipelines {
+        main: create_pipeline("real", None, true),
+        blend: create_pipeline("real blend", Some(wgpu::BlendState::ALPHA_BLENDING), false),
+        prepass,
+    }
+}
+
+fn draw_instance<'a>(
+    pass: &mut wgpu::RenderPass<'a>,
+    inst: &Instance,
+    local_bind_groups: &'a FxHashMap<LocalKey, wgpu::BindGroup>,
+    views: &super::Views,
+    size: wgpu::Extent3d,
+    context: &'a crate::Context,
+) {
+    let mesh = context.get_mesh(inst.mesh);
+
+    pass.set_bind_group(
+        1,
+        &local_bind_groups[&inst.local_key],
+        &[inst.locals_bl.offset],
+    );
+
+    pass.set_vertex_buffer(0, mesh.vertex_slice::<crate::Position>());
+    pass.set_vertex_buffer(1, mesh.vertex_slice::<crate::TexCoords>());
+    pass.set_vertex_buffer(2, mesh.vertex_slice::<crate::Normal>());
+
+    if let Some(ref is) = mesh.index_stream {
+        pass.set_index_buffer(mesh.buffer.slice(is.offset..), is.format);
+        views.draw(pass, size, |pass, instances| {
+            pass.draw_indexed(0..is.count, 0, instances)
+        });
+    } else {
+        views.draw(pass, size, |pass, instances| {
+            pass.draw(0..mesh.vertex_count, instances)
+        });
+    }
 }
 
 impl Real {
@@ -110,12 +469,27 @@ impl Real {
         config: &RealConfig,
         target_info: crate::TargetInfo,
         context: &crate::Context,
+    ) -> Self {
+        Self::new_multi_target(config, &[target_info], context)
+    }
+    /// Create a pass drawing into several targets at once:
+    /// the color, then optionally the world-space normals,
+    /// and the entity IDs, offset by one so that zero means no entity.
+    /// The normals need a float format, and the IDs `R32Uint`.
+    /// All the targets need the same sample count, which has to be 1
+    /// for the IDs, since integers can't be resolved. As the targets created
+    /// by the context use its sample count, drawing IDs needs a context
+    /// without multisampling.
+    ///
+    /// The pipelines for `target_infos` are created upfront,
+    /// but the pass isn't limited to drawing into these targets.
+    pub fn new_multi_target(
+        config: &RealConfig,
+        target_infos: &[crate::TargetInfo],
+        context: &crate::Context,
     ) -> Self {
         let d = context.device();
-        let shader_module = d.create_shader_module(&wgpu::ShaderModuleDescriptor {
-            label: Some("real"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("real.wgsl").into()),
-        });
+        let shader = super::StereoShader::new("real", include_str!("real.wgsl"), d);
 
         let globals_size = mem::size_of::<Globals>() as wgpu::BufferAddress;
         let global_bgl = d.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
@@ -126,7 +500,7 @@ impl Real {
                     visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
                     ty: wgpu::BindingType::Buffer {
                         ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
+                        has_dynamic_offset: true,
                         min_binding_size: wgpu::BufferSize::new(globals_size),
                     },
                     count: None,
@@ -149,11 +523,49 @@ impl Real {
                     ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
                     count: None,
                 },
+                // irradiance
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::Cube,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                // specular
+                wgpu::BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::Cube,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                // BRDF LUT
+                wgpu::BindGroupLayoutEntry {
+                    binding: 5,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
             ],
         });
+        // the camera globals are followed by the ones of each reflection
+        let globals_stride = super::align_up(
+            globals_size as u32,
+            d.limits().min_uniform_buffer_offset_alignment,
+        ) as wgpu::BufferAddress;
         let global_uniform_buf = d.create_buffer(&wgpu::BufferDescriptor {
             label: Some("real globals"),
-            size: globals_size,
+            size: globals_stride * (1 + config.max_reflections) as wgpu::BufferAddress,
             usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
             mapped_at_creation: false,
         });
@@ -167,26 +579,9 @@ impl Real {
             label: Some("real sampler"),
             min_filter: wgpu::FilterMode::Linear,
             mag_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
             ..Default::default()
         });
-        let global_bind_group = d.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("real globals"),
-            layout: &global_bgl,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: global_uniform_buf.as_entire_binding(),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: light_buf.as_entire_binding(),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 2,
-                    resource: wgpu::BindingResource::Sampler(&sampler),
-                },
-            ],
-        });
 
         let locals_size = mem::size_of::<Locals>() as wgpu::BufferAddress;
         let local_bgl = d.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
@@ -202,6 +597,7 @@ impl Real {
                     },
                     count: None,
                 },
+                // base color
                 wgpu::BindGroupLayoutEntry {
                     binding: 1,
                     visibility: wgpu::ShaderStages::FRAGMENT,
@@ -212,59 +608,104 @@ impl Real {
                     },
                     count: None,
                 },
+                // metallic-roughness
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                // occlusion
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                // emissive
+                wgpu::BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
             ],
         });
 
-        let pipelines = {
-            let pipeline_layout = d.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                label: Some("real"),
-                bind_group_layouts: &[&global_bgl, &local_bgl],
-                push_constant_ranges: &[],
-            });
-            let primitive = wgpu::PrimitiveState {
-                cull_mode: if config.cull_back_faces {
-                    Some(wgpu::Face::Back)
-                } else {
-                    None
+        let ssao_bgl = d.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("real ssao"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
                 },
-                ..Default::default()
-            };
-            let multisample = wgpu::MultisampleState {
-                count: target_info.sample_count,
-                ..Default::default()
-            };
+                count: None,
+            }],
+        });
 
-            Pipelines {
-                main: d.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                    label: Some("real"),
-                    layout: Some(&pipeline_layout),
-                    vertex: wgpu::VertexState {
-                        buffers: &[
-                            crate::Position::layout::<0>(),
-                            crate::TexCoords::layout::<1>(),
-                            crate::Normal::layout::<2>(),
-                        ],
-                        module: &shader_module,
-                        entry_point: "main_vs",
-                    },
-                    primitive,
-                    depth_stencil: Some(wgpu::DepthStencilState {
-                        format: DEPTH_FORMAT,
-                        depth_compare: wgpu::CompareFunction::LessEqual,
-                        depth_write_enabled: true,
-                        bias: Default::default(),
-                        stencil: Default::default(),
-                    }),
-                    multisample,
-                    fragment: Some(wgpu::FragmentState {
-                        targets: &[target_info.format.into()],
-                        module: &shader_module,
-                        entry_point: "main_fs",
-                    }),
-                    multiview: None,
-                }),
-            }
+        let reflection_bgl = d.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("real reflection"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            }],
+        });
+
+        let pipeline_layout = d.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("real"),
+            bind_group_layouts: &[&global_bgl, &local_bgl, &ssao_bgl, &reflection_bgl],
+            push_constant_ranges: &[],
+        });
+        let prepass_pipeline_layout = d.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("real prepass"),
+            bind_group_layouts: &[&global_bgl, &local_bgl],
+            push_constant_ranges: &[],
+        });
+        let default_culling = super::Culling::from_back_faces(config.cull_back_faces);
+        let targets_key = TargetsKey::new(
+            super::TargetKey::from_info(&target_infos[0]).with_default_depth(DEPTH_FORMAT),
+            target_infos[1..]
+                .iter()
+                .map(|info| (info.format, info.sample_count)),
+        );
+        let pipeline_key = PipelineKey {
+            targets: targets_key,
+            culling: default_culling,
+            front_face: wgpu::FrontFace::Ccw,
+            depth_compare: wgpu::CompareFunction::LessEqual,
         };
+        let mut pipelines = FxHashMap::default();
+        pipelines.insert(
+            pipeline_key,
+            create_pipelines(
+                &pipeline_layout,
+                &prepass_pipeline_layout,
+                &shader,
+                &pipeline_key,
+                d,
+            ),
+        );
 
         let blank_color_view = {
             let desc = wgpu::TextureDescriptor {
@@ -283,21 +724,129 @@ impl Real {
             let texture = d.create_texture_with_data(context.queue(), &desc, &[0xFF; 4]);
             texture.create_view(&wgpu::TextureViewDescriptor::default())
         };
+        let blank_cube_view = {
+            let desc = wgpu::TextureDescriptor {
+                label: Some("dummy cube"),
+                size: wgpu::Extent3d {
+                    width: 1,
+                    height: 1,
+                    depth_or_array_layers: 6,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba8Unorm,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING,
+            };
+            let texture = d.create_texture_with_data(context.queue(), &desc, &[0; 4 * 6]);
+            texture.create_view(&wgpu::TextureViewDescriptor {
+                dimension: Some(wgpu::TextureViewDimension::Cube),
+                ..Default::default()
+            })
+        };
+
+        let global_bind_group = create_global_bind_group(
+            &global_bgl,
+            GlobalResources {
+                uniform_buf: &global_uniform_buf,
+                light_buf: &light_buf,
+                sampler: &sampler,
+                irradiance_view: &blank_cube_view,
+                specular_view: &blank_cube_view,
+                brdf_lut_view: &blank_color_view,
+            },
+            d,
+        );
+        // without the occlusion, a blank texture keeps the ambient as is
+        let ssao_bind_group = create_ssao_bind_group(&ssao_bgl, &blank_color_view, d);
+        let blank_ssao_bind_group = create_ssao_bind_group(&ssao_bgl, &blank_color_view, d);
+        // the reflectivity of entities without a reflection is zero
+        let blank_reflection_bind_group = d.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("real reflection"),
+            layout: &reflection_bgl,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&blank_color_view),
+            }],
+        });
 
         Self {
-            depth_texture: None,
+            depth_texture: Default::default(),
             global_uniform_buf,
+            globals_stride,
             light_capacity: config.max_lights,
             light_buf,
+            global_bind_group_layout: global_bgl,
             global_bind_group,
             local_bind_group_layout: local_bgl,
             local_bind_groups: Default::default(),
             uniform_pool: super::BufferPool::uniform("real locals", d),
+            shader,
+            pipeline_layout,
+            prepass_pipeline_layout,
             pipelines,
+            default_culling,
+            ssao: config
+                .ambient_occlusion
+                .as_ref()
+                .map(|ssao_config| super::ssao::Ssao::new(ssao_config, d)),
+            ssao_bind_group_layout: ssao_bgl,
+            ssao_bind_group,
+            blank_ssao_bind_group,
+            reflection_bind_group_layout: reflection_bgl,
+            blank_reflection_bind_group,
+            reflection_capacity: config.max_reflections,
+            reflection_targets: Vec::new(),
+            reflection_views: Vec::new(),
+            section_cap: super::section::SectionCap::new(d),
+            sampler,
             blank_color_view,
+            blank_cube_view,
+            ambient: config.ambient,
+            environment_intensity: config.environment_intensity,
+            environment_mip_count: 0,
             instances: Vec::new(),
+            blended_instances: Vec::new(),
+            load_ops: bc::LoadOps::default(),
         }
     }
+
+    /// Choose whether the pass clears its target, or draws over
+    /// the contents left by the previous passes.
+    pub fn set_load_ops(&mut self, load_ops: bc::LoadOps) {
+        self.load_ops = load_ops;
+    }
+
+    /// Set the environment used for image-based lighting.
+    ///
+    /// Without an environment, the constant ambient color is used.
+    pub fn set_environment(
+        &mut self,
+        environment: Option<&super::Environment>,
+        context: &crate::Context,
+    ) {
+        let (irradiance_view, specular_view, brdf_lut_view) = match environment {
+            Some(env) => (&env.irradiance_view, &env.specular_view, &env.brdf_lut_view),
+            None => (
+                &self.blank_cube_view,
+                &self.blank_cube_view,
+                &self.blank_color_view,
+            ),
+        };
+        self.global_bind_group = create_global_bind_group(
+            &self.global_bind_group_layout,
+            GlobalResources {
+                uniform_buf: &self.global_uniform_buf,
+                light_buf: &self.light_buf,
+                sampler: &self.sampler,
+                irradiance_view,
+                specular_view,
+                brdf_lut_view,
+            },
+            context.device(),
+        );
+        self.environment_mip_count = environment.map_or(0, |env| env.specular_mip_count);
+    }
 }
 
 impl bc::Pass for Real {
@@ -309,41 +858,97 @@ impl bc::Pass for Real {
         context: &crate::Context,
     ) {
         let target = context.get_target(targets[0]);
+        let targets_key = TargetsKey::new(
+            super::TargetKey::new_layered(target)
+                .with_default_depth(super::section::depth_format(camera, DEPTH_FORMAT)),
+            targets[1..].iter().map(|&tr| {
+                let extra = context.get_target(tr);
+                assert_eq!(
+                    extra.size.depth_or_array_layers, target.size.depth_or_array_layers,
+                    "Real needs the same number of layers in all its targets"
+                );
+                (extra.format, extra.sample_count)
+            }),
+        );
+        let depth_format = targets_key.main.depth_format.unwrap();
+        let cap_color = super::section::cap_color(camera, depth_format);
+        let depth_compare = camera.depth_compare();
+        let pipeline_key = |culling| PipelineKey {
+            targets: targets_key,
+            culling,
+            front_face: wgpu::FrontFace::Ccw,
+            depth_compare,
+        };
         let device = context.device();
 
-        let reset_depth = match self.depth_texture {
-            Some((_, size)) => size != target.size,
-            None => true,
-        };
-        //TODO: abstract this part away
-        if reset_depth {
-            let texture = device.create_texture(&wgpu::TextureDescriptor {
-                label: Some("depth"),
-                dimension: wgpu::TextureDimension::D2,
-                format: DEPTH_FORMAT,
-                size: target.size,
-                sample_count: 1,
-                mip_level_count: 1,
-                usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            });
-            let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
-            self.depth_texture = Some((view, target.size));
-        }
+        self.depth_texture.prepare(target, depth_format, device);
+        self.shader.prepare(targets_key.main, device);
+
+        let views = super::Views::new(camera, target);
+        assert!(
+            camera.stereo.is_none() || self.ssao.is_none(),
+            "Real can't draw ambient occlusion for stereo cameras"
+        );
 
         let nodes = scene.bake();
         self.uniform_pool.reset();
         let queue = context.queue();
 
+        let m_proj = glam::Mat4::from(camera.target_projection_matrix(target.size));
+        let m_view_inv = glam::Mat4::from(nodes[camera.node].inverse_matrix());
+        let ambient_color = self.ambient.color.into_vec4();
+        let ambient = [
+            ambient_color[0] * self.ambient.intensity,
+            ambient_color[1] * self.ambient.intensity,
+            ambient_color[2] * self.ambient.intensity,
+            self.environment_mip_count as f32,
+        ];
+        let environment_intensity = self.environment_intensity;
+        let clip_planes = camera.clip_plane_array();
+        let make_globals = |view_proj: [[[f32; 4]; 4]; super::MAX_VIEWS],
+                            camera_pos: [f32; 4],
+                            size: wgpu::Extent3d| Globals {
+            view_proj,
+            camera_pos,
+            ambient,
+            inv_target_size: [1.0 / size.width as f32, 1.0 / size.height as f32],
+            environment_intensity,
+            pad: 0.0,
+            clip_planes,
+        };
+
         {
-            let m_proj = camera.projection_matrix(target.aspect());
-            let node = &nodes[camera.node];
-            let m_view_inv = node.inverse_matrix();
-            let m_final = glam::Mat4::from(m_proj) * glam::Mat4::from(m_view_inv);
-            let globals = Globals {
-                view_proj: m_final.to_cols_array_2d(),
-                camera_pos: node.pos_scale,
-            };
+            let globals = make_globals(
+                views.view_proj(m_view_inv, target.size),
+                nodes[camera.node].pos_scale,
+                target.size,
+            );
             queue.write_buffer(&self.global_uniform_buf, 0, bytemuck::bytes_of(&globals));
+
+            if let Some(ref mut ssao) = self.ssao {
+                if ssao.prepare(target.size, device) {
+                    self.ssao_bind_group = create_ssao_bind_group(
+                        &self.ssao_bind_group_layout,
+                        ssao.output_view(),
+                        device,
+                    );
+                }
+                // the occlusion is computed over the whole target,
+                // so the projection has to include the viewport mapping
+                let m_viewport = match camera.viewport {
+                    Some(vp) => glam::Mat4::from_scale_rotation_translation(
+                        glam::Vec3::new(vp.width, vp.height, 1.0),
+                        glam::Quat::IDENTITY,
+                        glam::Vec3::new(
+                            vp.width + 2.0 * vp.x - 1.0,
+                            1.0 - vp.height - 2.0 * vp.y,
+                            0.0,
+                        ),
+                    ),
+                    None => glam::Mat4::IDENTITY,
+                };
+                ssao.update(m_viewport * m_proj, m_view_inv, camera.far_depth(), queue);
+            }
         }
 
         let lights = scene
@@ -374,16 +979,46 @@ impl bc::Pass for Real {
         //TODO: we can do everything in a single pass if we use
         // some arena-based hashmap.
         self.instances.clear();
+        self.blended_instances.clear();
+        self.reflection_views.clear();
+        let cam_node = &nodes[camera.node];
+        let cam_pos = glam::Vec3::from_slice(&cam_node.pos_scale[..3]);
+        let cam_dir = glam::Quat::from_slice(&cam_node.rot) * -glam::Vec3::Z;
 
-        for (_, (entity, &color, mat)) in scene
+        for (handle, (entity, &color, mat, culling, visibility, reflection)) in scene
             .world
-            .query::<(&bc::Entity, &bc::Color, &Material)>()
+            .query::<(
+                &bc::Entity,
+                &bc::Color,
+                &Material,
+                Option<&super::Culling>,
+                Option<&bc::Visibility>,
+                Option<&super::Reflection>,
+            )>()
             .with::<bc::Vertex<crate::Position>>()
             .with::<bc::Vertex<crate::TexCoords>>()
             .with::<bc::Vertex<crate::Normal>>()
             .iter()
         {
+            if !camera.sees(visibility) {
+                continue;
+            }
             let space = &nodes[entity.node];
+            let cam_vector = glam::Vec3::from_slice(&space.pos_scale)
+                - glam::Vec3::from_slice(&cam_node.pos_scale);
+            let camera_distance = cam_vector.dot(cam_dir);
+
+            // reflections past the capacity are ignored
+            let reflection = match reflection {
+                Some(refl) if self.reflection_views.len() < self.reflection_capacity => {
+                    self.reflection_views.push(ReflectionView {
+                        plane: refl.world_plane(space, cam_pos),
+                        resolution_scale: refl.resolution_scale,
+                    });
+                    Some((self.reflection_views.len() - 1, refl.intensity))
+                }
+                _ => None,
+            };
 
             let locals = Locals {
                 pos_scale: space.pos_scale,
@@ -393,20 +1028,43 @@ impl bc::Pass for Real {
                 metallic_roughness_values: [mat.metallic_factor, mat.roughness_factor],
                 normal_scale: mat.normal_scale,
                 occlusion_strength: mat.occlusion_strength,
+                alpha_cutoff: match mat.alpha_mode {
+                    AlphaMode::Mask { cutoff } => cutoff,
+                    AlphaMode::Opaque | AlphaMode::Blend => 0.0,
+                },
+                entity_id: handle.id() + 1,
+                reflectivity: reflection.map_or(0.0, |(_, intensity)| intensity),
+                pad: 0.0,
             };
             let locals_bl = self.uniform_pool.alloc(&locals, queue);
 
             // pre-create local bind group, if needed
-            let key = LocalKey {
+            let maps = mat.maps();
+            let generation =
+                |map: Option<crate::ImageRef>| map.map_or(0, |ir| context.get_image(ir).generation);
+            let local_key = LocalKey {
                 uniform_buf_index: locals_bl.index,
-                base_color_map: mat.base_color_map,
+                maps,
+                generations: [
+                    generation(maps.base_color),
+                    generation(maps.metallic_roughness),
+                    generation(maps.occlusion),
+                    generation(maps.emissive),
+                ],
             };
+            if !self.local_bind_groups.contains_key(&local_key) {
+                // drop the bind groups of the replaced image views
+                self.local_bind_groups.retain(|other, _| {
+                    other.uniform_buf_index != local_key.uniform_buf_index
+                        || other.maps != local_key.maps
+                });
+            }
             let binding = self.uniform_pool.binding::<Locals>(locals_bl.index);
             let local_bgl = &self.local_bind_group_layout;
             let blank_color_view = &self.blank_color_view;
 
-            self.local_bind_groups.entry(key).or_insert_with(|| {
-                let base_color_view = match mat.base_color_map {
+            self.local_bind_groups.entry(local_key).or_insert_with(|| {
+                let map_view = |map: Option<crate::ImageRef>| match map {
                     Some(image) => &context.get_image(image).view,
                     None => blank_color_view,
                 };
@@ -420,25 +1078,172 @@ impl bc::Pass for Real {
                         },
                         wgpu::BindGroupEntry {
                             binding: 1,
-                            resource: wgpu::BindingResource::TextureView(base_color_view),
+                            resource: wgpu::BindingResource::TextureView(map_view(maps.base_color)),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 2,
+                            resource: wgpu::BindingResource::TextureView(map_view(
+                                maps.metallic_roughness,
+                            )),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 3,
+                            resource: wgpu::BindingResource::TextureView(map_view(maps.occlusion)),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 4,
+                            resource: wgpu::BindingResource::TextureView(map_view(maps.emissive)),
                         },
                     ],
                 })
             });
 
-            self.instances.push(Instance {
+            let culling = super::section::culling(
+                cap_color,
+                culling.cloned().unwrap_or(self.default_culling),
+            );
+            let (layout, prepass_layout, shader) = (
+                &self.pipeline_layout,
+                &self.prepass_pipeline_layout,
+                &self.shader,
+            );
+            let key = pipeline_key(culling);
+            self.pipelines
+                .entry(key)
+                .or_insert_with(|| create_pipelines(layout, prepass_layout, shader, &key, device));
+
+            let instance = Instance {
+                camera_distance,
+                culling,
                 mesh: entity.mesh,
                 locals_bl,
-                base_color_map: mat.base_color_map,
-            });
+                local_key,
+                reflection: reflection.map(|(index, _)| index),
+            };
+            match mat.alpha_mode {
+                AlphaMode::Opaque | AlphaMode::Mask { .. } => self.instances.push(instance),
+                AlphaMode::Blend => self.blended_instances.push(instance),
+            }
+        }
+
+        assert!(
+            camera.stereo.is_none() || self.reflection_views.is_empty(),
+            "Real can't draw reflections for stereo cameras"
+        );
+
+        // sort from back to front
+        self.blended_instances
+            .sort_by_key(|s| (s.camera_distance * -1000.0) as i64);
+
+        if cap_color.is_some() {
+            self.section_cap
+                .prepare(targets_key.main, targets_key.extra, device);
+        }
+
+        // the reflections are seen by the camera mirrored about their planes
+        let reflection_key = TargetsKey::new(
+            super::TargetKey {
+                format: target.format,
+                sample_count: 1,
+                depth_format: Some(super::reflection::DEPTH_FORMAT),
+                multiview: None,
+            },
+            iter::empty(),
+        );
+        let reflection_pipeline_key = |culling| PipelineKey {
+            targets: reflection_key,
+            culling,
+            front_face: wgpu::FrontFace::Cw,
+            depth_compare,
+        };
+        for (index, view) in self.reflection_views.iter().enumerate() {
+            let size = wgpu::Extent3d {
+                width: ((target.size.width as f32 * view.resolution_scale) as u32).max(1),
+                height: ((target.size.height as f32 * view.resolution_scale) as u32).max(1),
+                depth_or_array_layers: 1,
+            };
+            let is_valid = match self.reflection_targets.get(index) {
+                Some(rt) => rt.size == size && rt.format == target.format,
+                None => false,
+            };
+            if !is_valid {
+                let rt = super::reflection::ReflectionTarget::new(
+                    size,
+                    target.format,
+                    &self.reflection_bind_group_layout,
+                    device,
+                );
+                if index < self.reflection_targets.len() {
+                    self.reflection_targets[index] = rt;
+                } else {
+                    self.reflection_targets.push(rt);
+                }
+            }
+
+            let m_mirror = super::reflection::mirror_matrix(view.plane);
+            let m_view = m_view_inv * m_mirror;
+            // only the objects in front of the plane are reflected
+            let clip_plane = m_view.inverse().transpose() * view.plane;
+            let m_oblique =
+                super::reflection::oblique_projection(m_proj, clip_plane, camera.reverse_depth());
+            let globals = make_globals(
+                [(m_oblique * m_view).to_cols_array_2d(); super::MAX_VIEWS],
+                m_mirror.transform_point3(cam_pos).extend(1.0).into(),
+                size,
+            );
+            queue.write_buffer(
+                &self.global_uniform_buf,
+                self.globals_stride * (index + 1) as wgpu::BufferAddress,
+                bytemuck::bytes_of(&globals),
+            );
+        }
+        if !self.reflection_views.is_empty() {
+            // mirroring flips the winding of the triangles
+            let (layout, prepass_layout, shader) = (
+                &self.pipeline_layout,
+                &self.prepass_pipeline_layout,
+                &self.shader,
+            );
+            for inst in self.instances.iter().chain(self.blended_instances.iter()) {
+                let key = reflection_pipeline_key(inst.culling);
+                self.pipelines.entry(key).or_insert_with(|| {
+                    create_pipelines(layout, prepass_layout, shader, &key, device)
+                });
+            }
         }
 
+        let color_attachments = targets
+            .iter()
+            .enumerate()
+            .map(|(i, &tr)| {
+                let extra_target = context.get_target(tr);
+                wgpu::RenderPassColorAttachment {
+                    view: &extra_target.view,
+                    resolve_target: extra_target.resolve_view.as_ref(),
+                    ops: wgpu::Operations {
+                        // only the main color is cleared to the background
+                        load: if i == 0 {
+                            self.load_ops.color_op(camera.background)
+                        } else {
+                            self.load_ops.color_op(crate::Color(0))
+                        },
+                        store: true,
+                    },
+                }
+            })
+            .collect::<Vec<_>>();
+
         let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        for (index, rt) in self
+            .reflection_targets
+            .iter()
+            .take(self.reflection_views.len())
+            .enumerate()
         {
             let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("real"),
+                label: Some("real reflection"),
                 color_attachments: &[wgpu::RenderPassColorAttachment {
-                    view: &target.view,
+                    view: &rt.color_view,
                     resolve_target: None,
                     ops: wgpu::Operations {
                         load: wgpu::LoadOp::Clear(camera.background.into()),
@@ -446,41 +1251,133 @@ impl bc::Pass for Real {
                     },
                 }],
                 depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                    view: &self.depth_texture.as_ref().unwrap().0,
+                    view: &rt.depth_view,
                     depth_ops: Some(wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(1.0),
-                        store: true,
+                        load: wgpu::LoadOp::Clear(camera.far_depth()),
+                        store: false,
                     }),
                     stencil_ops: None,
                 }),
             });
+            camera.apply_viewport(&mut pass, rt.size);
 
-            pass.set_pipeline(&self.pipelines.main);
-            pass.set_bind_group(0, &self.global_bind_group, &[]);
+            let globals_offset = self.globals_stride * (index + 1) as wgpu::BufferAddress;
+            pass.set_bind_group(0, &self.global_bind_group, &[globals_offset as u32]);
+            pass.set_bind_group(2, &self.blank_ssao_bind_group, &[]);
+            pass.set_bind_group(3, &self.blank_reflection_bind_group, &[]);
 
-            for inst in self.instances.drain(..) {
-                let mesh = context.get_mesh(inst.mesh);
-
-                let key = LocalKey {
-                    uniform_buf_index: inst.locals_bl.index,
-                    base_color_map: inst.base_color_map,
-                };
-                let local_bg = &self.local_bind_groups[&key];
-                pass.set_bind_group(1, local_bg, &[inst.locals_bl.offset]);
+            let opaque = self.instances.iter().map(|inst| (false, inst));
+            let blended = self.blended_instances.iter().map(|inst| (true, inst));
+            for (blend, inst) in opaque.chain(blended) {
+                if inst.reflection.is_some() {
+                    continue;
+                }
+                let pipelines = &self.pipelines[&reflection_pipeline_key(inst.culling)];
+                pass.set_pipeline(if blend {
+                    &pipelines.blend
+                } else {
+                    &pipelines.main
+                });
+                draw_instance(
+                    &mut pass,
+                    inst,
+                    &self.local_bind_groups,
+                    &views,
+                    rt.size,
+                    context,
+                );
+            }
+        }
+        if let Some(ref ssao) = self.ssao {
+            // only the opaque objects contribute to the occlusion
+            {
+                let mut pass = ssao.begin_prepass(&mut encoder, camera.far_depth());
+                camera.apply_viewport(&mut pass, target.size);
+                pass.set_bind_group(0, &self.global_bind_group, &[0]);
+                for inst in self.instances.iter() {
+                    let pipelines = &self.pipelines[&pipeline_key(inst.culling)];
+                    pass.set_pipeline(&pipelines.prepass);
+                    draw_instance(
+                        &mut pass,
+                        inst,
+                        &self.local_bind_groups,
+                        &views,
+                        target.size,
+                        context,
+                    );
+                }
+            }
+            ssao.resolve(&mut encoder);
+        }
+        {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("real"),
+                color_attachments: &color_attachments,
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: self.depth_texture.view(target),
+                    depth_ops: Some(wgpu::Operations {
+                        load: self.load_ops.depth_op(camera.far_depth()),
+                        store: true,
+                    }),
+                    stencil_ops: super::section::stencil_ops(depth_format),
+                }),
+            });
+            views.apply_viewport(&mut pass, target.size);
+            pass.set_stencil_reference(super::section::INSIDE);
 
-                pass.set_vertex_buffer(0, mesh.vertex_slice::<crate::Position>());
-                pass.set_vertex_buffer(1, mesh.vertex_slice::<crate::TexCoords>());
-                pass.set_vertex_buffer(2, mesh.vertex_slice::<crate::Normal>());
+            pass.set_bind_group(0, &self.global_bind_group, &[0]);
+            pass.set_bind_group(2, &self.ssao_bind_group, &[]);
 
-                if let Some(ref is) = mesh.index_stream {
-                    pass.set_index_buffer(mesh.buffer.slice(is.offset..), is.format);
-                    pass.draw_indexed(0..is.count, 0, 0..1);
-                } else {
-                    pass.draw(0..mesh.vertex_count, 0..1);
+            for (blend, instances) in [
+                (false, &mut self.instances),
+                (true, &mut self.blended_instances),
+            ] {
+                // the caps are drawn before the transparent objects
+                if let (true, Some(color)) = (blend, cap_color) {
+                    let section_cap = &self.section_cap;
+                    views.draw(&mut pass, target.size, |pass, _| {
+                        section_cap.draw(pass, targets_key.main, targets_key.extra, color)
+                    });
+                    pass.set_bind_group(0, &self.global_bind_group, &[0]);
+                    pass.set_bind_group(2, &self.ssao_bind_group, &[]);
+                }
+                for inst in instances.drain(..) {
+                    let pipelines = &self.pipelines[&pipeline_key(inst.culling)];
+                    pass.set_pipeline(if blend {
+                        &pipelines.blend
+                    } else {
+                        &pipelines.main
+                    });
+                    let reflection_bind_group = match inst.reflection {
+                        Some(index) => &self.reflection_targets[index].bind_group,
+                        None => &self.blank_reflection_bind_group,
+                    };
+                    pass.set_bind_group(3, reflection_bind_group, &[]);
+                    draw_instance(
+                        &mut pass,
+                        &inst,
+                        &self.local_bind_groups,
+                        &views,
+                        target.size,
+                        context,
+                    );
                 }
             }
         }
 
-        queue.submit(Some(encoder.finish()));
+        context.submit(encoder.finish());
+    }
+
+    /// All the targets are drawn into, so the inputs of a graph node,
+    /// e.g. a shadow map, only order it after the passes writing them.
+    fn draw_node(
+        &mut self,
+        outputs: &[crate::TargetRef],
+        _inputs: &[crate::TargetRef],
+        scene: &crate::Scene,
+        camera: &crate::Camera,
+        context: &crate::Context,
+    ) {
+        self.draw(outputs, scene, camera, context);
     }
 }
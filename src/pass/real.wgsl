@@ -12,8 +12,13 @@ struct Varyings {
 };
 
 struct Globals {
-    view_proj: mat4x4<f32>;
+    view_proj: array<mat4x4<f32>, 2>;
     camerate_pos: vec4<f32>;
+    // RGB is the ambient color, A is the environment mip count
+    ambient: vec4<f32>;
+    inv_target_size: vec2<f32>;
+    environment_intensity: f32;
+    clip_planes: array<vec4<f32>, 4>;
 };
 [[group(0), binding(0)]]
 var<uniform> globals: Globals;
@@ -26,6 +31,9 @@ struct Locals {
     metallic_roughness_values: vec2<f32>;
     normal_scale: f32;
     occlusion_strength: f32;
+    alpha_cutoff: f32;
+    entity_id: u32;
+    reflectivity: f32;
 };
 [[group(1), binding(0)]]
 var<uniform> locals: Locals;
@@ -34,13 +42,24 @@ fn qrot(q: vec4<f32>, v: vec3<f32>) -> vec3<f32> {
     return v + 2.0*cross(q.xyz, cross(q.xyz,v) + q.w*v);
 }
 
+fn is_clipped(world: vec3<f32>) -> bool {
+    let p = vec4<f32>(world, 1.0);
+    let distances = vec4<f32>(
+        dot(globals.clip_planes[0], p),
+        dot(globals.clip_planes[1], p),
+        dot(globals.clip_planes[2], p),
+        dot(globals.clip_planes[3], p),
+    );
+    return any(distances < vec4<f32>(0.0));
+}
+
 [[stage(vertex)]]
-fn main_vs(in: Attributes) -> Varyings {
+fn main_vs(in: Attributes, [[builtin(instance_index)]] view: u32) -> Varyings {
     let world = locals.pos_scale.w * qrot(locals.rot, in.position) + locals.pos_scale.xyz;
     let normal = qrot(locals.rot, in.normal);
 
     return Varyings(
-        globals.view_proj * vec4<f32>(world, 1.0),
+        globals.view_proj[view] * vec4<f32>(world, 1.0),
         world,
         in.tex_coords,
         normal,
@@ -64,9 +83,29 @@ var<storage> lights: LightArray;
 
 [[group(0), binding(2)]]
 var sam: sampler;
+[[group(0), binding(3)]]
+var irradiance_map: texture_cube<f32>;
+[[group(0), binding(4)]]
+var specular_map: texture_cube<f32>;
+[[group(0), binding(5)]]
+var brdf_lut: texture_2d<f32>;
 
 [[group(1), binding(1)]]
 var base_color_map: texture_2d<f32>;
+[[group(1), binding(2)]]
+var metallic_roughness_map: texture_2d<f32>;
+[[group(1), binding(3)]]
+var occlusion_map: texture_2d<f32>;
+[[group(1), binding(4)]]
+var emissive_map: texture_2d<f32>;
+
+// screen-space ambient occlusion, in the red channel
+[[group(2), binding(0)]]
+var ssao_map: texture_2d<f32>;
+
+// planar reflection, in screen space
+[[group(3), binding(0)]]
+var reflection_map: texture_2d<f32>;
 
 
 struct PbrInfo {
@@ -115,13 +154,22 @@ fn ggx(pbr: PbrInfo) -> f32 {
     return roughness_sq / (PI * f * f);
 }
 
-[[stage(fragment)]]
-fn main_fs(in: Varyings) -> [[location(0)]] vec4<f32> {
-    let v = normalize(globals.camerate_pos.xyz - in.world_pos);
+// Back faces are shaded with the flipped normal.
+fn surface_normal(in: Varyings, front_facing: bool) -> vec3<f32> {
     let n = normalize(in.normal);
+    if (!front_facing) {
+        return -n;
+    }
+    return n;
+}
 
-    let perceptual_roughness = clamp(locals.metallic_roughness_values.y, MIN_ROUGHNESS, 1.0);
-    let metallic = clamp(locals.metallic_roughness_values.x, 0.0, 1.0);
+fn shade(in: Varyings, n: vec3<f32>) -> vec4<f32> {
+    let v = normalize(globals.camerate_pos.xyz - in.world_pos);
+
+    // Roughness is stored in the 'g' channel, metallic is stored in the 'b' channel.
+    let mr_sample = textureSample(metallic_roughness_map, sam, in.tex_coords);
+    let perceptual_roughness = clamp(locals.metallic_roughness_values.y * mr_sample.g, MIN_ROUGHNESS, 1.0);
+    let metallic = clamp(locals.metallic_roughness_values.x * mr_sample.b, 0.0, 1.0);
 
     let base_color = locals.base_color_factor * textureSample(base_color_map, sam, in.tex_coords);
 
@@ -177,5 +225,76 @@ fn main_fs(in: Varyings) -> [[location(0)]] vec4<f32> {
         color = color + ndotl * light.color_intensity.w * light.color_intensity.xyz * (diffuse_contrib + spec_contrib);
     }
 
+    var ambient = globals.ambient.xyz * base_color.xyz;
+    let env_mip_count = globals.ambient.w;
+    if (env_mip_count > 0.0) {
+        let ndotv = clamp(dot(n, v), 0.0, 1.0);
+        let reflection = reflect(-v, n);
+        let irradiance = textureSampleLevel(irradiance_map, sam, n, 0.0).xyz;
+        let lod = perceptual_roughness * (env_mip_count - 1.0);
+        let radiance = textureSampleLevel(specular_map, sam, reflection, lod).xyz;
+        let brdf = textureSampleLevel(brdf_lut, sam, vec2<f32>(ndotv, perceptual_roughness), 0.0).xy;
+        let diffuse = irradiance * diffuse_color;
+        let specular = radiance * (specular_color * brdf.x + brdf.y);
+        ambient = globals.environment_intensity * (diffuse + specular);
+    }
+
+    // Occlusion only applies to the indirect lighting.
+    let ao = textureSample(occlusion_map, sam, in.tex_coords).r;
+    let ssao_coord = min(vec2<i32>(in.clip_position.xy), textureDimensions(ssao_map) - 1);
+    let ssao = textureLoad(ssao_map, ssao_coord, 0).r;
+    color = color + ssao * mix(ambient, ambient * ao, locals.occlusion_strength);
+
+    let emissive = locals.emissive_factor.xyz * textureSample(emissive_map, sam, in.tex_coords).xyz;
+    color = color + emissive;
+
+    let reflected = textureSample(reflection_map, sam, in.clip_position.xy * globals.inv_target_size).xyz;
+    color = mix(color, reflected, locals.reflectivity);
+
+    // discarding has to happen after all the texture samples
+    if (base_color.a < locals.alpha_cutoff || is_clipped(in.world_pos)) {
+        discard;
+    }
     return vec4<f32>(color, base_color.a);
 }
+
+[[stage(fragment)]]
+fn main_fs(in: Varyings, [[builtin(front_facing)]] front_facing: bool) -> [[location(0)]] vec4<f32> {
+    return shade(in, surface_normal(in, front_facing));
+}
+
+struct NormalOutput {
+    [[location(0)]] color: vec4<f32>;
+    [[location(1)]] normal: vec4<f32>;
+};
+
+// Writes the world-space normals into the second target.
+[[stage(fragment)]]
+fn main_normal_fs(in: Varyings, [[builtin(front_facing)]] front_facing: bool) -> NormalOutput {
+    let n = surface_normal(in, front_facing);
+    return NormalOutput(shade(in, n), vec4<f32>(n, 0.0));
+}
+
+struct NormalIdOutput {
+    [[location(0)]] color: vec4<f32>;
+    [[location(1)]] normal: vec4<f32>;
+    [[location(2)]] entity_id: u32;
+};
+
+// Writes the normals and the entity IDs into the second and third targets.
+[[stage(fragment)]]
+fn main_normal_id_fs(in: Varyings, [[builtin(front_facing)]] front_facing: bool) -> NormalIdOutput {
+    let n = surface_normal(in, front_facing);
+    return NormalIdOutput(shade(in, n), vec4<f32>(n, 0.0), locals.entity_id);
+}
+
+// Writes the normals for the ambient occlusion.
+[[stage(fragment)]]
+fn prepass_fs(in: Varyings, [[builtin(front_facing)]] front_facing: bool) -> [[location(0)]] vec4<f32> {
+    let n = surface_normal(in, front_facing);
+    let alpha = locals.base_color_factor.a * textureSample(base_color_map, sam, in.tex_coords).a;
+    if (alpha < locals.alpha_cutoff || is_clipped(in.world_pos)) {
+        discard;
+    }
+    return vec4<f32>(n, 0.0);
+}
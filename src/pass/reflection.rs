@@ -0,0 +1,131 @@
+pub(super) const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+/// Planar reflection, attached as a component to an entity drawn by `Real`.
+///
+/// The scene is drawn mirrored about the plane of the entity,
+/// and the result is shown on its surface, making it a mirror,
+/// a polished floor, or a calm water surface.
+/// Reflective entities don't show up in the reflections.
+#[derive(Clone, Copy, Debug)]
+pub struct Reflection {
+    /// Normal of the mirror plane in the local space of the entity.
+    /// The default matches the plane geometry.
+    pub normal: mint::Vector3<f32>,
+    /// How much of the reflection is mixed into the surface color.
+    pub intensity: f32,
+    /// Resolution of the reflection, relative to the target.
+    pub resolution_scale: f32,
+}
+
+impl Default for Reflection {
+    fn default() -> Self {
+        Self {
+            normal: [0.0, 1.0, 0.0].into(),
+            intensity: 1.0,
+            resolution_scale: 0.5,
+        }
+    }
+}
+
+impl Reflection {
+    /// Mirror plane in world space, facing the camera.
+    pub(super) fn world_plane(&self, space: &bc::RawSpace, camera_pos: glam::Vec3) -> glam::Vec4 {
+        let rot = glam::Quat::from_slice(&space.rot);
+        let normal = (rot * glam::Vec3::from(self.normal)).normalize();
+        let point = glam::Vec3::from_slice(&space.pos_scale[..3]);
+        let plane = normal.extend(-normal.dot(point));
+        if plane.dot(camera_pos.extend(1.0)) < 0.0 {
+            -plane
+        } else {
+            plane
+        }
+    }
+}
+
+/// Matrix mirroring the space about a plane.
+pub(super) fn mirror_matrix(plane: glam::Vec4) -> glam::Mat4 {
+    let n = plane.truncate();
+    let d = plane.w;
+    glam::Mat4::from_cols(
+        (glam::Vec3::X - 2.0 * n.x * n).extend(0.0),
+        (glam::Vec3::Y - 2.0 * n.y * n).extend(0.0),
+        (glam::Vec3::Z - 2.0 * n.z * n).extend(0.0),
+        (-2.0 * d * n).extend(1.0),
+    )
+}
+
+/// Modify the projection so that its near plane matches the clip plane,
+/// given in view space, while keeping the far plane as close as possible.
+///
+/// See "Oblique View Frustum Depth Projection and Clipping" by Eric Lengyel.
+pub(super) fn oblique_projection(
+    proj: glam::Mat4,
+    clip_plane: glam::Vec4,
+    reverse_depth: bool,
+) -> glam::Mat4 {
+    let far_depth = if reverse_depth { 0.0 } else { 1.0 };
+    let corner = proj.inverse()
+        * glam::Vec4::new(clip_plane.x.signum(), clip_plane.y.signum(), far_depth, 1.0);
+    let scaled_plane = clip_plane / clip_plane.dot(corner);
+    let mut rows = proj.transpose();
+    // with the reversed depth, the near plane is where Z equals W
+    *rows.col_mut(2) = if reverse_depth {
+        rows.col(3) - scaled_plane
+    } else {
+        scaled_plane
+    };
+    rows.transpose()
+}
+
+/// Offscreen target receiving a reflection.
+pub(super) struct ReflectionTarget {
+    pub size: wgpu::Extent3d,
+    pub format: wgpu::TextureFormat,
+    pub color_view: wgpu::TextureView,
+    pub depth_view: wgpu::TextureView,
+    pub bind_group: wgpu::BindGroup,
+}
+
+impl ReflectionTarget {
+    pub(super) fn new(
+        size: wgpu::Extent3d,
+        format: wgpu::TextureFormat,
+        layout: &wgpu::BindGroupLayout,
+        device: &wgpu::Device,
+    ) -> Self {
+        let create_view = |label, format, usage| {
+            device
+                .create_texture(&wgpu::TextureDescriptor {
+                    label: Some(label),
+                    size,
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format,
+                    usage: wgpu::TextureUsages::RENDER_ATTACHMENT | usage,
+                })
+                .create_view(&wgpu::TextureViewDescriptor::default())
+        };
+        let color_view = create_view("reflection", format, wgpu::TextureUsages::TEXTURE_BINDING);
+        let depth_view = create_view(
+            "reflection depth",
+            DEPTH_FORMAT,
+            wgpu::TextureUsages::empty(),
+        );
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("reflection"),
+            layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&color_view),
+            }],
+        });
+        Self {
+            size,
+            format,
+            color_view,
+            depth_view,
+            bind_group,
+        }
+    }
+}
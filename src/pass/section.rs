@@ -0,0 +1,189 @@
+use fxhash::FxHashMap;
+
+/// Depth format of the passes drawing section caps, which need a stencil.
+pub(super) const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth24PlusStencil8;
+/// Stencil value of the pixels where the inside of a mesh is visible.
+pub(super) const INSIDE: u32 = 1;
+
+fn has_stencil(format: wgpu::TextureFormat) -> bool {
+    format == wgpu::TextureFormat::Depth24PlusStencil8
+}
+
+/// Depth format of a pass drawing for this camera into a target without depth.
+pub(super) fn depth_format(
+    camera: &bc::Camera,
+    default: wgpu::TextureFormat,
+) -> wgpu::TextureFormat {
+    if camera.clip_cap.is_some() {
+        DEPTH_FORMAT
+    } else {
+        default
+    }
+}
+
+/// Color of the section caps, if the camera has any to draw
+/// and the depth buffer can mark them.
+pub(super) fn cap_color(
+    camera: &bc::Camera,
+    depth_format: wgpu::TextureFormat,
+) -> Option<bc::Color> {
+    match camera.clip_cap {
+        Some(color) if !camera.clip_planes.is_empty() && has_stencil(depth_format) => Some(color),
+        _ => None,
+    }
+}
+
+/// Culling of an entity, keeping the back faces where the caps are drawn.
+pub(super) fn culling(cap_color: Option<bc::Color>, culling: super::Culling) -> super::Culling {
+    match cap_color {
+        Some(_) => super::Culling::None,
+        None => culling,
+    }
+}
+
+/// Stencil state of the pipelines drawing meshes, marking the pixels
+/// where the back faces remain visible after the clipping.
+pub(super) fn stencil_state(depth_format: wgpu::TextureFormat) -> wgpu::StencilState {
+    if !has_stencil(depth_format) {
+        return wgpu::StencilState::default();
+    }
+    let face = |pass_op| wgpu::StencilFaceState {
+        compare: wgpu::CompareFunction::Always,
+        fail_op: wgpu::StencilOperation::Keep,
+        depth_fail_op: wgpu::StencilOperation::Keep,
+        pass_op,
+    };
+    wgpu::StencilState {
+        front: face(wgpu::StencilOperation::Zero),
+        back: face(wgpu::StencilOperation::Replace),
+        read_mask: !0,
+        write_mask: !0,
+    }
+}
+
+/// Stencil operations of a pass, clearing the marks of the previous ones.
+pub(super) fn stencil_ops(depth_format: wgpu::TextureFormat) -> Option<wgpu::Operations<u32>> {
+    if has_stencil(depth_format) {
+        Some(wgpu::Operations {
+            load: wgpu::LoadOp::Clear(0),
+            store: false,
+        })
+    } else {
+        None
+    }
+}
+
+/// Pipelines covering the pixels marked in the stencil with the cap color,
+/// after all the meshes of a pass are drawn.
+pub(super) struct SectionCap {
+    shader_module: wgpu::ShaderModule,
+    pipeline_layout: wgpu::PipelineLayout,
+    pipelines:
+        FxHashMap<(super::TargetKey, [Option<wgpu::TextureFormat>; 2]), wgpu::RenderPipeline>,
+}
+
+impl SectionCap {
+    pub(super) fn new(device: &wgpu::Device) -> Self {
+        let shader_module = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label: Some("section"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("section.wgsl").into()),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("section"),
+            bind_group_layouts: &[],
+            push_constant_ranges: &[],
+        });
+        Self {
+            shader_module,
+            pipeline_layout,
+            pipelines: Default::default(),
+        }
+    }
+
+    /// Create the pipeline for a target, with the formats of the extra
+    /// color attachments of the pass left untouched.
+    pub(super) fn prepare(
+        &mut self,
+        target: super::TargetKey,
+        extra: [Option<wgpu::TextureFormat>; 2],
+        device: &wgpu::Device,
+    ) {
+        let (layout, shader_module) = (&self.pipeline_layout, &self.shader_module);
+        self.pipelines.entry((target, extra)).or_insert_with(|| {
+            let keep = wgpu::StencilFaceState {
+                compare: wgpu::CompareFunction::Equal,
+                fail_op: wgpu::StencilOperation::Keep,
+                depth_fail_op: wgpu::StencilOperation::Keep,
+                pass_op: wgpu::StencilOperation::Keep,
+            };
+            let mut targets = vec![wgpu::ColorTargetState {
+                format: target.format,
+                blend: Some(wgpu::BlendState {
+                    color: wgpu::BlendComponent {
+                        src_factor: wgpu::BlendFactor::Constant,
+                        dst_factor: wgpu::BlendFactor::Zero,
+                        operation: wgpu::BlendOperation::Add,
+                    },
+                    alpha: wgpu::BlendComponent::REPLACE,
+                }),
+                write_mask: wgpu::ColorWrites::ALL,
+            }];
+            targets.extend(
+                extra
+                    .iter()
+                    .flatten()
+                    .map(|&format| wgpu::ColorTargetState {
+                        format,
+                        blend: None,
+                        write_mask: wgpu::ColorWrites::empty(),
+                    }),
+            );
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("section cap"),
+                layout: Some(layout),
+                vertex: wgpu::VertexState {
+                    buffers: &[],
+                    module: shader_module,
+                    entry_point: "main_vs",
+                },
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: target.depth_format.unwrap_or(DEPTH_FORMAT),
+                    depth_compare: wgpu::CompareFunction::Always,
+                    depth_write_enabled: false,
+                    bias: Default::default(),
+                    stencil: wgpu::StencilState {
+                        front: keep,
+                        back: keep,
+                        read_mask: !0,
+                        write_mask: 0,
+                    },
+                }),
+                multisample: wgpu::MultisampleState {
+                    count: target.sample_count,
+                    ..Default::default()
+                },
+                fragment: Some(wgpu::FragmentState {
+                    targets: &targets,
+                    module: shader_module,
+                    entry_point: "main_fs",
+                }),
+                multiview: target.multiview,
+            })
+        });
+    }
+
+    /// Cover the marked pixels, using the pipeline created by `prepare`.
+    pub(super) fn draw<'a>(
+        &'a self,
+        pass: &mut wgpu::RenderPass<'a>,
+        target: super::TargetKey,
+        extra: [Option<wgpu::TextureFormat>; 2],
+        color: bc::Color,
+    ) {
+        pass.set_pipeline(&self.pipelines[&(target, extra)]);
+        pass.set_stencil_reference(INSIDE);
+        pass.set_blend_constant(color.into());
+        pass.draw(0..3, 0..1);
+    }
+}
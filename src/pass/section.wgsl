@@ -0,0 +1,16 @@
+// The cap color comes from the blend constant,
+// so the fragment stage only needs to cover the marked pixels.
+
+[[stage(vertex)]]
+fn main_vs([[builtin(vertex_index)]] index: u32) -> [[builtin(position)]] vec4<f32> {
+    let pos = vec2<f32>(
+        f32(i32(index & 1u) * 4 - 1),
+        f32(i32(index & 2u) * 2 - 1),
+    );
+    return vec4<f32>(pos, 0.0, 1.0);
+}
+
+[[stage(fragment)]]
+fn main_fs() -> [[location(0)]] vec4<f32> {
+    return vec4<f32>(1.0);
+}
@@ -0,0 +1,190 @@
+use bc::ContextDetail as _;
+use std::mem;
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct Globals {
+    inv_view_proj: [[f32; 4]; 4],
+}
+
+/// Background pass, drawing a panorama around the camera.
+///
+/// Only the camera rotation is taken into account, so the sky
+/// looks infinitely far away.
+pub struct Sky {
+    global_uniform_buf: wgpu::Buffer,
+    global_bind_group: wgpu::BindGroup,
+    shader_module: wgpu::ShaderModule,
+    pipeline_layout: wgpu::PipelineLayout,
+    pipelines: super::FullscreenPipelines,
+    load_ops: bc::LoadOps,
+}
+
+impl Sky {
+    pub fn new(panorama: super::Panorama, context: &crate::Context) -> Self {
+        Self::new_offscreen(panorama, context.surface_info().unwrap(), context)
+    }
+    pub fn new_offscreen(
+        panorama: super::Panorama,
+        target_info: crate::TargetInfo,
+        context: &crate::Context,
+    ) -> Self {
+        let d = context.device();
+        let shader_module = d.create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label: Some("sky"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("sky.wgsl").into()),
+        });
+
+        let (image, image_binding, view_dimension, entry_point) = match panorama {
+            super::Panorama::Cube(image) => (image, 2, wgpu::TextureViewDimension::Cube, "cube_fs"),
+            super::Panorama::Equirect(image) => {
+                (image, 3, wgpu::TextureViewDimension::D2, "equirect_fs")
+            }
+        };
+
+        let globals_size = mem::size_of::<Globals>() as wgpu::BufferAddress;
+        let global_bgl = d.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("sky globals"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: wgpu::BufferSize::new(globals_size),
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: image_binding,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let global_uniform_buf = d.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("sky globals"),
+            size: globals_size,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let sampler = d.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("sky sampler"),
+            address_mode_u: wgpu::AddressMode::Repeat,
+            min_filter: wgpu::FilterMode::Linear,
+            mag_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let global_bind_group = d.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("sky globals"),
+            layout: &global_bgl,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: global_uniform_buf.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: image_binding,
+                    resource: wgpu::BindingResource::TextureView(&context.get_image(image).view),
+                },
+            ],
+        });
+
+        let pipeline_layout = d.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("sky"),
+            bind_group_layouts: &[&global_bgl],
+            push_constant_ranges: &[],
+        });
+        let mut pipelines = super::FullscreenPipelines::new("sky", entry_point);
+        pipelines.get(
+            super::TargetKey::from_info(&target_info),
+            &pipeline_layout,
+            &shader_module,
+            d,
+        );
+
+        Self {
+            global_uniform_buf,
+            global_bind_group,
+            shader_module,
+            pipeline_layout,
+            pipelines,
+            load_ops: bc::LoadOps::default(),
+        }
+    }
+
+    /// Choose whether the sky clears the target color first.
+    /// The pass has no depth, so the depth load is ignored.
+    pub fn set_load_ops(&mut self, load_ops: bc::LoadOps) {
+        self.load_ops = load_ops;
+    }
+}
+
+impl bc::Pass for Sky {
+    fn draw(
+        &mut self,
+        targets: &[crate::TargetRef],
+        scene: &crate::Scene,
+        camera: &crate::Camera,
+        context: &crate::Context,
+    ) {
+        super::assert_mono(camera, "Sky");
+        let target = context.get_target(targets[0]);
+        let device = context.device();
+        let queue = context.queue();
+
+        {
+            let nodes = scene.bake();
+            let m_proj = glam::Mat4::from(camera.target_projection_matrix(target.size));
+            let m_rot = glam::Mat4::from_quat(glam::Quat::from_array(nodes[camera.node].rot));
+            let globals = Globals {
+                inv_view_proj: (m_rot * m_proj.inverse()).to_cols_array_2d(),
+            };
+            queue.write_buffer(&self.global_uniform_buf, 0, bytemuck::bytes_of(&globals));
+        }
+
+        let pipeline = self.pipelines.get(
+            super::TargetKey::new(target),
+            &self.pipeline_layout,
+            &self.shader_module,
+            device,
+        );
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("sky"),
+                color_attachments: &[wgpu::RenderPassColorAttachment {
+                    view: &target.view,
+                    resolve_target: target.resolve_view.as_ref(),
+                    ops: wgpu::Operations {
+                        load: self.load_ops.color_op(camera.background),
+                        store: true,
+                    },
+                }],
+                depth_stencil_attachment: None,
+            });
+            camera.apply_viewport(&mut pass, target.size);
+            pass.set_pipeline(pipeline);
+            pass.set_bind_group(0, &self.global_bind_group, &[]);
+            pass.draw(0..3, 0..1);
+        }
+
+        context.submit(encoder.finish());
+    }
+}
@@ -0,0 +1,50 @@
+struct Globals {
+    inv_view_proj: mat4x4<f32>;
+};
+[[group(0), binding(0)]]
+var<uniform> globals: Globals;
+
+[[group(0), binding(1)]]
+var sam: sampler;
+[[group(0), binding(2)]]
+var cube_map: texture_cube<f32>;
+[[group(0), binding(3)]]
+var equirect_map: texture_2d<f32>;
+
+let PI: f32 = 3.141592653589793;
+
+struct Varyings {
+    [[builtin(position)]] clip_pos: vec4<f32>;
+    [[location(0)]] dir: vec3<f32>;
+};
+
+[[stage(vertex)]]
+fn main_vs([[builtin(vertex_index)]] index: u32) -> Varyings {
+    let pos = vec2<f32>(
+        f32(i32(index & 1u) * 4 - 1),
+        f32(i32(index & 2u) * 2 - 1),
+    );
+    // the view matrix has no translation, so any point
+    // on the ray from the camera gives us the direction
+    let world = globals.inv_view_proj * vec4<f32>(pos, 0.5, 1.0);
+    return Varyings(
+        vec4<f32>(pos, 0.0, 1.0),
+        world.xyz / world.w,
+    );
+}
+
+[[stage(fragment)]]
+fn cube_fs(in: Varyings) -> [[location(0)]] vec4<f32> {
+    return textureSample(cube_map, sam, normalize(in.dir));
+}
+
+[[stage(fragment)]]
+fn equirect_fs(in: Varyings) -> [[location(0)]] vec4<f32> {
+    let dir = normalize(in.dir);
+    // has to match the mapping in "environment.wgsl"
+    let uv = vec2<f32>(
+        0.5 + 0.5 * atan2(dir.z, dir.x) / PI,
+        acos(clamp(dir.y, -1.0, 1.0)) / PI,
+    );
+    return textureSampleLevel(equirect_map, sam, uv, 0.0);
+}
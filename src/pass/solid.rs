@@ -2,12 +2,13 @@ use bc::ContextDetail as _;
 use fxhash::FxHashMap;
 use std::mem;
 
-const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth24Plus;
+const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
 
 #[repr(C)]
 #[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
 struct Globals {
-    view_proj: [[f32; 4]; 4],
+    view_proj: [[[f32; 4]; 4]; super::MAX_VIEWS],
+    clip_planes: [[f32; 4]; bc::MAX_CLIP_PLANES],
 }
 
 #[repr(C)]
@@ -37,13 +38,60 @@ impl Default for SolidConfig {
 }
 
 pub struct Solid {
-    depth_texture: Option<(wgpu::TextureView, wgpu::Extent3d)>,
+    depth_texture: super::DepthTexture,
     global_uniform_buf: wgpu::Buffer,
     global_bind_group: wgpu::BindGroup,
     local_bind_group_layout: wgpu::BindGroupLayout,
     local_bind_groups: FxHashMap<LocalKey, wgpu::BindGroup>,
     uniform_pool: super::BufferPool,
-    pipeline: wgpu::RenderPipeline,
+    shader: super::StereoShader,
+    pipeline_layout: wgpu::PipelineLayout,
+    pipelines:
+        FxHashMap<(super::TargetKey, super::Culling, wgpu::CompareFunction), wgpu::RenderPipeline>,
+    default_culling: super::Culling,
+    section_cap: super::section::SectionCap,
+    load_ops: bc::LoadOps,
+}
+
+fn create_pipeline(
+    layout: &wgpu::PipelineLayout,
+    shader_module: &wgpu::ShaderModule,
+    target: super::TargetKey,
+    culling: super::Culling,
+    depth_compare: wgpu::CompareFunction,
+    device: &wgpu::Device,
+) -> wgpu::RenderPipeline {
+    let depth_format = target.depth_format.unwrap_or(DEPTH_FORMAT);
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("solid"),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            buffers: &[crate::Position::layout::<0>()],
+            module: shader_module,
+            entry_point: "main_vs",
+        },
+        primitive: wgpu::PrimitiveState {
+            cull_mode: culling.face(),
+            ..Default::default()
+        },
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: depth_format,
+            depth_compare,
+            depth_write_enabled: true,
+            bias: Default::default(),
+            stencil: super::section::stencil_state(depth_format),
+        }),
+        multisample: wgpu::MultisampleState {
+            count: target.sample_count,
+            ..Default::default()
+        },
+        fragment: Some(wgpu::FragmentState {
+            targets: &[target.format.into()],
+            module: shader_module,
+            entry_point: "main_fs",
+        }),
+        multiview: target.multiview,
+    })
 }
 
 impl Solid {
@@ -56,17 +104,14 @@ impl Solid {
         context: &crate::Context,
     ) -> Self {
         let d = context.device();
-        let shader_module = d.create_shader_module(&wgpu::ShaderModuleDescriptor {
-            label: Some("solid"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("solid.wgsl").into()),
-        });
+        let shader = super::StereoShader::new("solid", include_str!("solid.wgsl"), d);
 
         let globals_size = mem::size_of::<Globals>() as wgpu::BufferAddress;
         let global_bgl = d.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             label: Some("solid globals"),
             entries: &[wgpu::BindGroupLayoutEntry {
                 binding: 0,
-                visibility: wgpu::ShaderStages::VERTEX,
+                visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
                 ty: wgpu::BindingType::Buffer {
                     ty: wgpu::BufferBindingType::Uniform,
                     has_dynamic_offset: false,
@@ -110,48 +155,46 @@ impl Solid {
             bind_group_layouts: &[&global_bgl, &local_bgl],
             push_constant_ranges: &[],
         });
-        let pipeline = d.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("solid"),
-            layout: Some(&pipeline_layout),
-            vertex: wgpu::VertexState {
-                buffers: &[crate::Position::layout::<0>()],
-                module: &shader_module,
-                entry_point: "main_vs",
-            },
-            primitive: wgpu::PrimitiveState {
-                cull_mode: if config.cull_back_faces {
-                    Some(wgpu::Face::Back)
-                } else {
-                    None
-                },
-                ..Default::default()
-            },
-            depth_stencil: Some(wgpu::DepthStencilState {
-                format: DEPTH_FORMAT,
-                depth_compare: wgpu::CompareFunction::LessEqual,
-                depth_write_enabled: true,
-                bias: Default::default(),
-                stencil: Default::default(),
-            }),
-            multisample: wgpu::MultisampleState::default(),
-            fragment: Some(wgpu::FragmentState {
-                targets: &[target_info.format.into()],
-                module: &shader_module,
-                entry_point: "main_fs",
-            }),
-            multiview: None,
-        });
+        let default_culling = super::Culling::from_back_faces(config.cull_back_faces);
+        let target_key = super::TargetKey::from_info(&target_info).with_default_depth(DEPTH_FORMAT);
+        let mut pipelines = FxHashMap::default();
+        pipelines.insert(
+            (
+                target_key,
+                default_culling,
+                wgpu::CompareFunction::LessEqual,
+            ),
+            create_pipeline(
+                &pipeline_layout,
+                &shader.module,
+                target_key,
+                default_culling,
+                wgpu::CompareFunction::LessEqual,
+                d,
+            ),
+        );
 
         Self {
-            depth_texture: None,
+            depth_texture: Default::default(),
             global_uniform_buf,
             global_bind_group,
             local_bind_group_layout: local_bgl,
             local_bind_groups: Default::default(),
             uniform_pool: super::BufferPool::uniform("solid locals", d),
-            pipeline,
+            shader,
+            pipeline_layout,
+            pipelines,
+            default_culling,
+            section_cap: super::section::SectionCap::new(d),
+            load_ops: bc::LoadOps::default(),
         }
     }
+
+    /// Choose whether the pass clears its target, or draws over
+    /// the contents left by the previous passes.
+    pub fn set_load_ops(&mut self, load_ops: bc::LoadOps) {
+        self.load_ops = load_ops;
+    }
 }
 
 impl bc::Pass for Solid {
@@ -163,48 +206,70 @@ impl bc::Pass for Solid {
         context: &crate::Context,
     ) {
         let target = context.get_target(targets[0]);
+        let target_key = super::TargetKey::new_layered(target)
+            .with_default_depth(super::section::depth_format(camera, DEPTH_FORMAT));
+        let depth_format = target_key.depth_format.unwrap();
+        let cap_color = super::section::cap_color(camera, depth_format);
+        let depth_compare = camera.depth_compare();
         let device = context.device();
 
-        let reset_depth = match self.depth_texture {
-            Some((_, size)) => size != target.size,
-            None => true,
-        };
-        if reset_depth {
-            let texture = device.create_texture(&wgpu::TextureDescriptor {
-                label: Some("depth"),
-                dimension: wgpu::TextureDimension::D2,
-                format: DEPTH_FORMAT,
-                size: target.size,
-                sample_count: 1,
-                mip_level_count: 1,
-                usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            });
-            let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
-            self.depth_texture = Some((view, target.size));
-        }
+        self.depth_texture.prepare(target, depth_format, device);
+        self.shader.prepare(target_key, device);
 
+        let views = super::Views::new(camera, target);
         let nodes = scene.bake();
         self.uniform_pool.reset();
         let queue = context.queue();
 
         {
-            let m_proj = camera.projection_matrix(target.aspect());
             let m_view_inv = nodes[camera.node].inverse_matrix();
-            let m_final = glam::Mat4::from(m_proj) * glam::Mat4::from(m_view_inv);
             let globals = Globals {
-                view_proj: m_final.to_cols_array_2d(),
+                view_proj: views.view_proj(m_view_inv.into(), target.size),
+                clip_planes: camera.clip_plane_array(),
             };
             queue.write_buffer(&self.global_uniform_buf, 0, bytemuck::bytes_of(&globals));
         }
 
-        // pre-create the bind groups so that we don't need to do it on the fly
-        let local_bgl = &self.local_bind_group_layout;
-        let entity_count = scene
+        // pre-create the pipelines and bind groups so that we don't need to do it on the fly
+        let mut entity_count = 0;
+        for (_, (_, _, culling, visibility)) in scene
             .world
-            .query::<(&bc::Entity, &bc::Color)>()
+            .query::<(
+                &bc::Entity,
+                &bc::Color,
+                Option<&super::Culling>,
+                Option<&bc::Visibility>,
+            )>()
             .with::<bc::Vertex<crate::Position>>()
             .iter()
-            .count();
+        {
+            if !camera.sees(visibility) {
+                continue;
+            }
+            let culling = super::section::culling(
+                cap_color,
+                culling.cloned().unwrap_or(self.default_culling),
+            );
+            let (layout, shader_module) = (&self.pipeline_layout, self.shader.module(target_key));
+            self.pipelines
+                .entry((target_key, culling, depth_compare))
+                .or_insert_with(|| {
+                    create_pipeline(
+                        layout,
+                        shader_module,
+                        target_key,
+                        culling,
+                        depth_compare,
+                        device,
+                    )
+                });
+            entity_count += 1;
+        }
+        if cap_color.is_some() {
+            self.section_cap.prepare(target_key, [None; 2], device);
+        }
+
+        let local_bgl = &self.local_bind_group_layout;
         let uniform_pool_size = self
             .uniform_pool
             .prepare_for_count::<Locals>(entity_count, device);
@@ -231,35 +296,50 @@ impl bc::Pass for Solid {
                 label: Some("solid"),
                 color_attachments: &[wgpu::RenderPassColorAttachment {
                     view: &target.view,
-                    resolve_target: None,
+                    resolve_target: target.resolve_view.as_ref(),
                     ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(camera.background.into()),
+                        load: self.load_ops.color_op(camera.background),
                         store: true,
                     },
                 }],
                 depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                    view: &self.depth_texture.as_ref().unwrap().0,
+                    view: self.depth_texture.view(target),
                     depth_ops: Some(wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(1.0),
+                        load: self.load_ops.depth_op(camera.far_depth()),
                         store: true,
                     }),
-                    stencil_ops: None,
+                    stencil_ops: super::section::stencil_ops(depth_format),
                 }),
             });
-            pass.set_pipeline(&self.pipeline);
+            views.apply_viewport(&mut pass, target.size);
+            pass.set_stencil_reference(super::section::INSIDE);
             pass.set_bind_group(0, &self.global_bind_group, &[]);
 
-            for (_, (entity, color)) in scene
+            for (_, (entity, color, culling, visibility)) in scene
                 .world
-                .query::<(&bc::Entity, &bc::Color)>()
+                .query::<(
+                    &bc::Entity,
+                    &bc::Color,
+                    Option<&super::Culling>,
+                    Option<&bc::Visibility>,
+                )>()
                 .with::<bc::Vertex<crate::Position>>()
                 .iter()
             {
+                if !camera.sees(visibility) {
+                    continue;
+                }
+                let culling = super::section::culling(
+                    cap_color,
+                    culling.cloned().unwrap_or(self.default_culling),
+                );
+                pass.set_pipeline(&self.pipelines[&(target_key, culling, depth_compare)]);
+
                 let space = &nodes[entity.node];
                 let locals = Locals {
                     pos_scale: space.pos_scale,
                     rot: space.rot,
-                    color: color.into_vec4_gamma(),
+                    color: color.into_vec4_linear(),
                 };
                 let bl = self.uniform_pool.alloc(&locals, queue);
 
@@ -275,13 +355,24 @@ impl bc::Pass for Solid {
 
                 if let Some(ref is) = mesh.index_stream {
                     pass.set_index_buffer(mesh.buffer.slice(is.offset..), is.format);
-                    pass.draw_indexed(0..is.count, 0, 0..1);
+                    views.draw(&mut pass, target.size, |pass, instances| {
+                        pass.draw_indexed(0..is.count, 0, instances)
+                    });
                 } else {
-                    pass.draw(0..mesh.vertex_count, 0..1);
+                    views.draw(&mut pass, target.size, |pass, instances| {
+                        pass.draw(0..mesh.vertex_count, instances)
+                    });
                 }
             }
+
+            if let Some(color) = cap_color {
+                let section_cap = &self.section_cap;
+                views.draw(&mut pass, target.size, |pass, _| {
+                    section_cap.draw(pass, target_key, [None; 2], color)
+                });
+            }
         }
 
-        queue.submit(Some(encoder.finish()));
+        context.submit(encoder.finish());
     }
 }
@@ -3,7 +3,8 @@ struct Vertex {
 };
 
 struct Globals {
-    view_proj: mat4x4<f32>;
+    view_proj: array<mat4x4<f32>, 2>;
+    clip_planes: array<vec4<f32>, 4>;
 };
 [[group(0), binding(0)]]
 var<uniform> globals: Globals;
@@ -20,13 +21,32 @@ fn qrot(q: vec4<f32>, v: vec3<f32>) -> vec3<f32> {
     return v + 2.0*cross(q.xyz, cross(q.xyz,v) + q.w*v);
 }
 
+fn is_clipped(world: vec3<f32>) -> bool {
+    let p = vec4<f32>(world, 1.0);
+    let distances = vec4<f32>(
+        dot(globals.clip_planes[0], p),
+        dot(globals.clip_planes[1], p),
+        dot(globals.clip_planes[2], p),
+        dot(globals.clip_planes[3], p),
+    );
+    return any(distances < vec4<f32>(0.0));
+}
+
+struct Varyings {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] world: vec3<f32>;
+};
+
 [[stage(vertex)]]
-fn main_vs(in: Vertex) -> [[builtin(position)]] vec4<f32> {
+fn main_vs(in: Vertex, [[builtin(instance_index)]] view: u32) -> Varyings {
     let world = locals.pos_scale.w * qrot(locals.rot, in.pos) + locals.pos_scale.xyz;
-    return globals.view_proj * vec4<f32>(world, 1.0);
+    return Varyings(globals.view_proj[view] * vec4<f32>(world, 1.0), world);
 }
 
 [[stage(fragment)]]
-fn main_fs() -> [[location(0)]] vec4<f32> {
+fn main_fs(in: Varyings) -> [[location(0)]] vec4<f32> {
+    if (is_clipped(in.world)) {
+        discard;
+    }
     return locals.color;
 }
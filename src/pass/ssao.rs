@@ -0,0 +1,366 @@
+use std::mem;
+
+pub(super) const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+pub(super) const NORMAL_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+const OCCLUSION_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R8Unorm;
+const MAX_SAMPLES: usize = 64;
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct Params {
+    proj: [[f32; 4]; 4],
+    inv_proj: [[f32; 4]; 4],
+    view: [[f32; 4]; 4],
+    radius: f32,
+    bias: f32,
+    intensity: f32,
+    sample_count: u32,
+    blur_radius: i32,
+    far_depth: f32,
+    pad: [i32; 2],
+    kernel: [[f32; 4]; MAX_SAMPLES],
+}
+
+/// Screen-space ambient occlusion, darkening the indirect lighting
+/// in creases and around contact points.
+#[derive(Clone, Debug)]
+pub struct SsaoConfig {
+    /// World-space radius of the sampled hemisphere.
+    pub radius: f32,
+    /// Depth offset preventing flat surfaces from occluding themselves.
+    pub bias: f32,
+    /// Exponent applied to the result, making the occlusion stronger.
+    pub intensity: f32,
+    /// Number of samples per pixel, up to 64.
+    pub sample_count: u32,
+    /// Radius of the box blur removing the sampling noise, in pixels.
+    /// Zero disables the blur.
+    pub blur_radius: u32,
+}
+
+impl Default for SsaoConfig {
+    fn default() -> Self {
+        Self {
+            radius: 0.5,
+            bias: 0.025,
+            intensity: 1.0,
+            sample_count: 16,
+            blur_radius: 2,
+        }
+    }
+}
+
+/// Sample offsets in the unit hemisphere around +Z,
+/// concentrated towards the center.
+fn generate_kernel(count: usize) -> [[f32; 4]; MAX_SAMPLES] {
+    let golden_angle = std::f32::consts::PI * (3.0 - 5f32.sqrt());
+    let mut kernel = [[0.0; 4]; MAX_SAMPLES];
+    for (i, k) in kernel[..count].iter_mut().enumerate() {
+        let cos_theta = 1.0 - (i as f32 + 0.5) / count as f32;
+        let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+        let phi = i as f32 * golden_angle;
+        // base-2 radical inverse, for well-distributed lengths
+        let t = (i as u32).reverse_bits() as f32 / 4294967296.0;
+        let scale = 0.1 + 0.9 * t * t;
+        *k = [
+            scale * sin_theta * phi.cos(),
+            scale * sin_theta * phi.sin(),
+            scale * cos_theta,
+            0.0,
+        ];
+    }
+    kernel
+}
+
+struct Targets {
+    size: wgpu::Extent3d,
+    depth_view: wgpu::TextureView,
+    normal_view: wgpu::TextureView,
+    raw_view: wgpu::TextureView,
+    blurred_view: wgpu::TextureView,
+    occlusion_bind_group: wgpu::BindGroup,
+    blur_bind_group: wgpu::BindGroup,
+}
+
+/// Ambient occlusion computed from the depth and normals
+/// rendered by a prepass of the owning pass.
+pub(super) struct Ssao {
+    params_buf: wgpu::Buffer,
+    occlusion_bind_group_layout: wgpu::BindGroupLayout,
+    blur_bind_group_layout: wgpu::BindGroupLayout,
+    occlusion_pipeline: wgpu::RenderPipeline,
+    blur_pipeline: wgpu::RenderPipeline,
+    targets: Option<Targets>,
+    config: SsaoConfig,
+    kernel: [[f32; 4]; MAX_SAMPLES],
+}
+
+fn draw_fullscreen(
+    encoder: &mut wgpu::CommandEncoder,
+    view: &wgpu::TextureView,
+    pipeline: &wgpu::RenderPipeline,
+    bind_group: &wgpu::BindGroup,
+) {
+    let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some("ssao"),
+        color_attachments: &[wgpu::RenderPassColorAttachment {
+            view,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(wgpu::Color::WHITE),
+                store: true,
+            },
+        }],
+        depth_stencil_attachment: None,
+    });
+    pass.set_pipeline(pipeline);
+    pass.set_bind_group(0, bind_group, &[]);
+    pass.draw(0..3, 0..1);
+}
+
+impl Ssao {
+    pub(super) fn new(config: &SsaoConfig, device: &wgpu::Device) -> Self {
+        let shader_module = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label: Some("ssao"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("ssao.wgsl").into()),
+        });
+
+        let params_size = mem::size_of::<Params>() as wgpu::BufferAddress;
+        let params_buf = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("ssao params"),
+            size: params_size,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let params_entry = wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: wgpu::BufferSize::new(params_size),
+            },
+            count: None,
+        };
+        let texture_entry = |binding, sample_type| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let unfilterable = wgpu::TextureSampleType::Float { filterable: false };
+        let occlusion_bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("ssao"),
+            entries: &[
+                params_entry,
+                texture_entry(1, wgpu::TextureSampleType::Depth),
+                texture_entry(2, unfilterable),
+            ],
+        });
+        let blur_bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("ssao blur"),
+            entries: &[params_entry, texture_entry(3, unfilterable)],
+        });
+
+        let create_pipeline = |entry_point, bind_group_layout| {
+            let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some(entry_point),
+                bind_group_layouts: &[bind_group_layout],
+                push_constant_ranges: &[],
+            });
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(entry_point),
+                layout: Some(&layout),
+                vertex: wgpu::VertexState {
+                    buffers: &[],
+                    module: &shader_module,
+                    entry_point: "main_vs",
+                },
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                fragment: Some(wgpu::FragmentState {
+                    targets: &[OCCLUSION_FORMAT.into()],
+                    module: &shader_module,
+                    entry_point,
+                }),
+                multiview: None,
+            })
+        };
+        let occlusion_pipeline = create_pipeline("ssao_fs", &occlusion_bgl);
+        let blur_pipeline = create_pipeline("blur_fs", &blur_bgl);
+
+        let sample_count = (config.sample_count as usize).clamp(1, MAX_SAMPLES);
+        Self {
+            params_buf,
+            occlusion_bind_group_layout: occlusion_bgl,
+            blur_bind_group_layout: blur_bgl,
+            occlusion_pipeline,
+            blur_pipeline,
+            targets: None,
+            config: SsaoConfig {
+                sample_count: sample_count as u32,
+                ..config.clone()
+            },
+            kernel: generate_kernel(sample_count),
+        }
+    }
+
+    /// Make sure the prepass targets match the size.
+    /// Returns true if they were re-created.
+    pub(super) fn prepare(&mut self, size: wgpu::Extent3d, device: &wgpu::Device) -> bool {
+        if let Some(ref targets) = self.targets {
+            if targets.size == size {
+                return false;
+            }
+        }
+
+        let create_view = |label, format, usage| {
+            device
+                .create_texture(&wgpu::TextureDescriptor {
+                    label: Some(label),
+                    size,
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format,
+                    usage: wgpu::TextureUsages::RENDER_ATTACHMENT | usage,
+                })
+                .create_view(&wgpu::TextureViewDescriptor::default())
+        };
+        let binding = wgpu::TextureUsages::TEXTURE_BINDING;
+        let depth_view = create_view("ssao depth", DEPTH_FORMAT, binding);
+        let normal_view = create_view("ssao normals", NORMAL_FORMAT, binding);
+        let raw_view = create_view("ssao raw", OCCLUSION_FORMAT, binding);
+        let blurred_view = create_view("ssao blurred", OCCLUSION_FORMAT, binding);
+
+        let occlusion_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("ssao"),
+            layout: &self.occlusion_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: self.params_buf.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&depth_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(&normal_view),
+                },
+            ],
+        });
+        let blur_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("ssao blur"),
+            layout: &self.blur_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: self.params_buf.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(&raw_view),
+                },
+            ],
+        });
+
+        self.targets = Some(Targets {
+            size,
+            depth_view,
+            normal_view,
+            raw_view,
+            blurred_view,
+            occlusion_bind_group,
+            blur_bind_group,
+        });
+        true
+    }
+
+    pub(super) fn update(
+        &self,
+        proj: glam::Mat4,
+        view: glam::Mat4,
+        far_depth: f32,
+        queue: &wgpu::Queue,
+    ) {
+        let params = Params {
+            proj: proj.to_cols_array_2d(),
+            inv_proj: proj.inverse().to_cols_array_2d(),
+            view: view.to_cols_array_2d(),
+            radius: self.config.radius,
+            bias: self.config.bias,
+            intensity: self.config.intensity,
+            sample_count: self.config.sample_count,
+            blur_radius: self.config.blur_radius as i32,
+            far_depth,
+            pad: [0; 2],
+            kernel: self.kernel,
+        };
+        queue.write_buffer(&self.params_buf, 0, bytemuck::bytes_of(&params));
+    }
+
+    /// Start the prepass, which renders world-space normals and depth.
+    pub(super) fn begin_prepass<'a>(
+        &'a self,
+        encoder: &'a mut wgpu::CommandEncoder,
+        far_depth: f32,
+    ) -> wgpu::RenderPass<'a> {
+        let targets = self.targets.as_ref().unwrap();
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("ssao prepass"),
+            color_attachments: &[wgpu::RenderPassColorAttachment {
+                view: &targets.normal_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: true,
+                },
+            }],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &targets.depth_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(far_depth),
+                    store: true,
+                }),
+                stencil_ops: None,
+            }),
+        })
+    }
+
+    /// Compute the occlusion out of the prepass results.
+    pub(super) fn resolve(&self, encoder: &mut wgpu::CommandEncoder) {
+        let targets = self.targets.as_ref().unwrap();
+        draw_fullscreen(
+            encoder,
+            &targets.raw_view,
+            &self.occlusion_pipeline,
+            &targets.occlusion_bind_group,
+        );
+        if self.config.blur_radius != 0 {
+            draw_fullscreen(
+                encoder,
+                &targets.blurred_view,
+                &self.blur_pipeline,
+                &targets.blur_bind_group,
+            );
+        }
+    }
+
+    /// Final occlusion texture, with visibility in the red channel.
+    pub(super) fn output_view(&self) -> &wgpu::TextureView {
+        let targets = self.targets.as_ref().unwrap();
+        if self.config.blur_radius != 0 {
+            &targets.blurred_view
+        } else {
+            &targets.raw_view
+        }
+    }
+}
@@ -0,0 +1,102 @@
+let MAX_SAMPLES: u32 = 64u;
+
+struct Params {
+    proj: mat4x4<f32>;
+    inv_proj: mat4x4<f32>;
+    view: mat4x4<f32>;
+    radius: f32;
+    bias: f32;
+    intensity: f32;
+    sample_count: u32;
+    blur_radius: i32;
+    far_depth: f32;
+    kernel: array<vec4<f32>, 64>;
+};
+[[group(0), binding(0)]]
+var<uniform> params: Params;
+
+[[group(0), binding(1)]]
+var depth_map: texture_depth_2d;
+[[group(0), binding(2)]]
+var normal_map: texture_2d<f32>;
+[[group(0), binding(3)]]
+var occlusion_map: texture_2d<f32>;
+
+struct Varyings {
+    [[builtin(position)]] clip_pos: vec4<f32>;
+};
+
+[[stage(vertex)]]
+fn main_vs([[builtin(vertex_index)]] index: u32) -> Varyings {
+    let pos = vec2<f32>(
+        f32(i32(index & 1u) * 4 - 1),
+        f32(i32(index & 2u) * 2 - 1),
+    );
+    return Varyings(vec4<f32>(pos, 0.0, 1.0));
+}
+
+fn view_position(uv: vec2<f32>, depth: f32) -> vec3<f32> {
+    let ndc = vec4<f32>(2.0 * uv.x - 1.0, 1.0 - 2.0 * uv.y, depth, 1.0);
+    let pos = params.inv_proj * ndc;
+    return pos.xyz / pos.w;
+}
+
+// Interleaved gradient noise by Jorge Jimenez.
+fn noise(pixel: vec2<f32>) -> f32 {
+    return fract(52.9829189 * fract(dot(pixel, vec2<f32>(0.06711056, 0.00583715))));
+}
+
+[[stage(fragment)]]
+fn ssao_fs(in: Varyings) -> [[location(0)]] vec4<f32> {
+    let dim = textureDimensions(depth_map);
+    let size = vec2<f32>(dim);
+    let coord = vec2<i32>(in.clip_pos.xy);
+    let depth = textureLoad(depth_map, coord, 0);
+    if (depth == params.far_depth) {
+        return vec4<f32>(1.0);
+    }
+
+    let pos = view_position(in.clip_pos.xy / size, depth);
+    let world_normal = textureLoad(normal_map, coord, 0).xyz;
+    let n = normalize((params.view * vec4<f32>(world_normal, 0.0)).xyz);
+
+    // orient the sample kernel around the normal, randomly rotated per pixel
+    let angle = 6.283185307 * noise(in.clip_pos.xy);
+    let random = vec3<f32>(cos(angle), sin(angle), 0.0);
+    let t = normalize(random - n * dot(random, n));
+    let tbn = mat3x3<f32>(t, cross(n, t), n);
+
+    var occlusion = 0.0;
+    let count = min(params.sample_count, MAX_SAMPLES);
+    for (var i = 0u; i < count; i = i + 1u) {
+        let sample_pos = pos + params.radius * (tbn * params.kernel[i].xyz);
+        let clip = params.proj * vec4<f32>(sample_pos, 1.0);
+        let ndc = clip.xy / clip.w;
+        let sample_uv = vec2<f32>(0.5 * ndc.x + 0.5, 0.5 - 0.5 * ndc.y);
+        let sample_coord = clamp(vec2<i32>(sample_uv * size), vec2<i32>(0), dim - 1);
+        let scene_z = view_position(sample_uv, textureLoad(depth_map, sample_coord, 0)).z;
+        // ignore occluders far outside of the radius
+        let range_check = smoothStep(0.0, 1.0, params.radius / abs(pos.z - scene_z));
+        if (scene_z >= sample_pos.z + params.bias) {
+            occlusion = occlusion + range_check;
+        }
+    }
+
+    let visibility = 1.0 - occlusion / f32(max(count, 1u));
+    return vec4<f32>(pow(visibility, params.intensity));
+}
+
+[[stage(fragment)]]
+fn blur_fs(in: Varyings) -> [[location(0)]] vec4<f32> {
+    let dim = textureDimensions(occlusion_map);
+    let coord = vec2<i32>(in.clip_pos.xy);
+    var sum = 0.0;
+    for (var y = -params.blur_radius; y <= params.blur_radius; y = y + 1) {
+        for (var x = -params.blur_radius; x <= params.blur_radius; x = x + 1) {
+            let c = clamp(coord + vec2<i32>(x, y), vec2<i32>(0), dim - 1);
+            sum = sum + textureLoad(occlusion_map, c, 0).r;
+        }
+    }
+    let width = f32(2 * params.blur_radius + 1);
+    return vec4<f32>(sum / (width * width));
+}
@@ -0,0 +1,366 @@
+use bc::ContextDetail as _;
+use std::{mem, time};
+use wgpu::util::DeviceExt as _;
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct Params {
+    operator: u32,
+    min_log_luminance: f32,
+    max_log_luminance: f32,
+    compensation: f32,
+    adaptation: f32,
+    pad: [f32; 3],
+}
+
+/// Curve mapping HDR colors into the displayable range.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ToneMapping {
+    Aces,
+    Reinhard,
+    Agx,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AutoExposure {
+    /// Exposure compensation, in stops.
+    pub compensation: f32,
+    /// Lowest average luminance to adapt to.
+    pub min_luminance: f32,
+    /// Highest average luminance to adapt to.
+    pub max_luminance: f32,
+    /// Adaptation speed, per second.
+    pub speed: f32,
+}
+
+impl Default for AutoExposure {
+    fn default() -> Self {
+        Self {
+            compensation: 0.0,
+            min_luminance: 0.01,
+            max_luminance: 20.0,
+            speed: 2.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Exposure {
+    /// Fixed exposure, in stops.
+    Manual(f32),
+    /// Exposure adapting to the average luminance of the image.
+    Auto(AutoExposure),
+}
+
+#[derive(Debug)]
+pub struct TonemapConfig {
+    pub tone_mapping: ToneMapping,
+    pub exposure: Exposure,
+}
+
+impl Default for TonemapConfig {
+    fn default() -> Self {
+        Self {
+            tone_mapping: ToneMapping::Aces,
+            exposure: Exposure::Manual(0.0),
+        }
+    }
+}
+
+struct BindGroups {
+    render: wgpu::BindGroup,
+    adapt: wgpu::BindGroup,
+    source: crate::TargetRef,
+    size: wgpu::Extent3d,
+}
+
+/// Post-processing pass that resolves an HDR target
+/// into the displayable range.
+///
+/// If drawn with a second target, e.g. as an input in a `RenderGraph`,
+/// it's used as the source instead of the one given at creation.
+pub struct Tonemap {
+    source: crate::TargetRef,
+    params_buf: wgpu::Buffer,
+    exposure_buf: wgpu::Buffer,
+    render_bind_group_layout: wgpu::BindGroupLayout,
+    adapt_bind_group_layout: wgpu::BindGroupLayout,
+    bind_groups: Option<BindGroups>,
+    shader_module: wgpu::ShaderModule,
+    render_pipeline_layout: wgpu::PipelineLayout,
+    render_pipelines: super::FullscreenPipelines,
+    adapt_pipeline: wgpu::ComputePipeline,
+    tone_mapping: ToneMapping,
+    exposure: Exposure,
+    last_adaptation: Option<time::Instant>,
+}
+
+impl Tonemap {
+    pub fn new(config: &TonemapConfig, source: crate::TargetRef, context: &crate::Context) -> Self {
+        Self::new_offscreen(config, source, context.surface_info().unwrap(), context)
+    }
+    pub fn new_offscreen(
+        config: &TonemapConfig,
+        source: crate::TargetRef,
+        target_info: crate::TargetInfo,
+        context: &crate::Context,
+    ) -> Self {
+        let d = context.device();
+        let shader_module = d.create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label: Some("tonemap"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("tonemap.wgsl").into()),
+        });
+
+        let params_size = mem::size_of::<Params>() as wgpu::BufferAddress;
+        let params_buf = d.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("tonemap params"),
+            size: params_size,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let exposure_buf = d.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("tonemap exposure"),
+            contents: bytemuck::bytes_of(&1.0f32),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let params_entry = |visibility| wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: wgpu::BufferSize::new(params_size),
+            },
+            count: None,
+        };
+        let source_entry = |visibility| wgpu::BindGroupLayoutEntry {
+            binding: 1,
+            visibility,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let render_bgl = d.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("tonemap"),
+            entries: &[
+                params_entry(wgpu::ShaderStages::FRAGMENT),
+                source_entry(wgpu::ShaderStages::FRAGMENT),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: wgpu::BufferSize::new(4),
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let adapt_bgl = d.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("tonemap adaptation"),
+            entries: &[
+                params_entry(wgpu::ShaderStages::COMPUTE),
+                source_entry(wgpu::ShaderStages::COMPUTE),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: wgpu::BufferSize::new(4),
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let render_pipeline_layout = d.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("tonemap"),
+            bind_group_layouts: &[&render_bgl],
+            push_constant_ranges: &[],
+        });
+        let mut render_pipelines = super::FullscreenPipelines::new("tonemap", "main_fs");
+        render_pipelines.get(
+            super::TargetKey::from_info(&target_info),
+            &render_pipeline_layout,
+            &shader_module,
+            d,
+        );
+
+        let adapt_pipeline_layout = d.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("tonemap adaptation"),
+            bind_group_layouts: &[&adapt_bgl],
+            push_constant_ranges: &[],
+        });
+        let adapt_pipeline = d.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("tonemap adaptation"),
+            layout: Some(&adapt_pipeline_layout),
+            module: &shader_module,
+            entry_point: "adapt_cs",
+        });
+
+        Self {
+            source,
+            params_buf,
+            exposure_buf,
+            render_bind_group_layout: render_bgl,
+            adapt_bind_group_layout: adapt_bgl,
+            bind_groups: None,
+            shader_module,
+            render_pipeline_layout,
+            render_pipelines,
+            adapt_pipeline,
+            tone_mapping: config.tone_mapping,
+            exposure: config.exposure,
+            last_adaptation: None,
+        }
+    }
+
+    pub fn set_tone_mapping(&mut self, tone_mapping: ToneMapping) {
+        self.tone_mapping = tone_mapping;
+    }
+
+    pub fn set_exposure(&mut self, exposure: Exposure) {
+        self.exposure = exposure;
+        self.last_adaptation = None;
+    }
+}
+
+impl bc::Pass for Tonemap {
+    fn draw(
+        &mut self,
+        targets: &[crate::TargetRef],
+        _scene: &crate::Scene,
+        _camera: &crate::Camera,
+        context: &crate::Context,
+    ) {
+        let target = context.get_target(targets[0]);
+        let source_ref = targets.get(1).copied().unwrap_or(self.source);
+        let source = context.get_target(source_ref);
+        let device = context.device();
+        let queue = context.queue();
+
+        // the source view is re-created when it's resized
+        let reset_bind_groups = match self.bind_groups {
+            Some(ref bg) => bg.source != source_ref || bg.size != source.size,
+            None => true,
+        };
+        if reset_bind_groups {
+            self.bind_groups = Some(BindGroups {
+                render: device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("tonemap"),
+                    layout: &self.render_bind_group_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: self.params_buf.as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: wgpu::BindingResource::TextureView(source.resolved_view()),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 2,
+                            resource: self.exposure_buf.as_entire_binding(),
+                        },
+                    ],
+                }),
+                adapt: device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("tonemap adaptation"),
+                    layout: &self.adapt_bind_group_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: self.params_buf.as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: wgpu::BindingResource::TextureView(source.resolved_view()),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 3,
+                            resource: self.exposure_buf.as_entire_binding(),
+                        },
+                    ],
+                }),
+                source: source_ref,
+                size: source.size,
+            });
+        }
+        let bind_groups = self.bind_groups.as_ref().unwrap();
+
+        let mut params = Params {
+            operator: match self.tone_mapping {
+                ToneMapping::Aces => 0,
+                ToneMapping::Reinhard => 1,
+                ToneMapping::Agx => 2,
+            },
+            min_log_luminance: 0.0,
+            max_log_luminance: 0.0,
+            compensation: 0.0,
+            adaptation: 0.0,
+            pad: [0.0; 3],
+        };
+        match self.exposure {
+            Exposure::Manual(stops) => {
+                queue.write_buffer(&self.exposure_buf, 0, bytemuck::bytes_of(&stops.exp2()));
+            }
+            Exposure::Auto(ref auto) => {
+                let now = time::Instant::now();
+                // jump straight to the target exposure on the first frame
+                params.adaptation = match self.last_adaptation {
+                    Some(last) => {
+                        let delta = now.duration_since(last).as_secs_f32();
+                        1.0 - (-delta * auto.speed).exp()
+                    }
+                    None => 1.0,
+                };
+                params.min_log_luminance = auto.min_luminance.log2();
+                params.max_log_luminance = auto.max_luminance.log2();
+                params.compensation = auto.compensation;
+                self.last_adaptation = Some(now);
+            }
+        }
+        queue.write_buffer(&self.params_buf, 0, bytemuck::bytes_of(&params));
+
+        let pipeline = self.render_pipelines.get(
+            super::TargetKey::new(target),
+            &self.render_pipeline_layout,
+            &self.shader_module,
+            device,
+        );
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        if let Exposure::Auto(_) = self.exposure {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("tonemap adaptation"),
+            });
+            pass.set_pipeline(&self.adapt_pipeline);
+            pass.set_bind_group(0, &bind_groups.adapt, &[]);
+            pass.dispatch(1, 1, 1);
+        }
+        {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("tonemap"),
+                color_attachments: &[wgpu::RenderPassColorAttachment {
+                    view: &target.view,
+                    resolve_target: target.resolve_view.as_ref(),
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: true,
+                    },
+                }],
+                depth_stencil_attachment: None,
+            });
+            pass.set_pipeline(pipeline);
+            pass.set_bind_group(0, &bind_groups.render, &[]);
+            pass.draw(0..3, 0..1);
+        }
+
+        context.submit(encoder.finish());
+    }
+}
@@ -0,0 +1,119 @@
+struct Params {
+    operator: u32;
+    min_log_luminance: f32;
+    max_log_luminance: f32;
+    compensation: f32;
+    // blend factor towards the new exposure this frame
+    adaptation: f32;
+};
+[[group(0), binding(0)]]
+var<uniform> params: Params;
+[[group(0), binding(1)]]
+var source: texture_2d<f32>;
+
+struct Exposure {
+    value: f32;
+};
+[[group(0), binding(2)]]
+var<storage> exposure: Exposure;
+[[group(0), binding(3)]]
+var<storage, read_write> exposure_rw: Exposure;
+
+let MIDDLE_GRAY: f32 = 0.18;
+let SAMPLE_GRID: u32 = 64u;
+let GROUP_SIZE: u32 = 256u;
+
+fn luminance(color: vec3<f32>) -> f32 {
+    return dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+}
+
+var<workgroup> partial_sums: array<f32, 256>;
+
+[[stage(compute), workgroup_size(256)]]
+fn adapt_cs([[builtin(local_invocation_index)]] index: u32) {
+    let size = textureDimensions(source);
+    var sum = 0.0;
+    for (var i = index; i < SAMPLE_GRID * SAMPLE_GRID; i = i + GROUP_SIZE) {
+        let cell = vec2<i32>(i32(i % SAMPLE_GRID), i32(i / SAMPLE_GRID));
+        let coord = (2 * cell + 1) * size / i32(2u * SAMPLE_GRID);
+        let color = textureLoad(source, coord, 0).xyz;
+        sum = sum + clamp(log2(luminance(color) + 0.0001), params.min_log_luminance, params.max_log_luminance);
+    }
+    partial_sums[index] = sum;
+    workgroupBarrier();
+
+    for (var stride = GROUP_SIZE / 2u; stride > 0u; stride = stride / 2u) {
+        if (index < stride) {
+            partial_sums[index] = partial_sums[index] + partial_sums[index + stride];
+        }
+        workgroupBarrier();
+    }
+
+    if (index == 0u) {
+        let average = partial_sums[0] / f32(SAMPLE_GRID * SAMPLE_GRID);
+        let wanted = MIDDLE_GRAY * exp2(params.compensation - average);
+        exposure_rw.value = mix(exposure_rw.value, wanted, params.adaptation);
+    }
+}
+
+struct Varyings {
+    [[builtin(position)]] clip_pos: vec4<f32>;
+};
+
+[[stage(vertex)]]
+fn main_vs([[builtin(vertex_index)]] index: u32) -> Varyings {
+    let pos = vec2<f32>(
+        f32(i32(index & 1u) * 4 - 1),
+        f32(i32(index & 2u) * 2 - 1),
+    );
+    return Varyings(vec4<f32>(pos, 0.0, 1.0));
+}
+
+// Curve fit by Krzysztof Narkowicz.
+fn aces(x: vec3<f32>) -> vec3<f32> {
+    let num = x * (2.51 * x + 0.03);
+    let den = x * (2.43 * x + 0.59) + 0.14;
+    return clamp(num / den, vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+fn reinhard(x: vec3<f32>) -> vec3<f32> {
+    return x / (1.0 + luminance(x));
+}
+
+// Minimal AgX implementation by Benjamin Wrensch.
+fn agx(color: vec3<f32>) -> vec3<f32> {
+    let inset = mat3x3<f32>(
+        vec3<f32>(0.842479062253094, 0.0423282422610123, 0.0423756549057051),
+        vec3<f32>(0.0784335999999992, 0.878468636469772, 0.0784336),
+        vec3<f32>(0.0792237451477643, 0.0791661274605434, 0.879142973793104),
+    );
+    let outset = mat3x3<f32>(
+        vec3<f32>(1.19687900512017, -0.0528968517574562, -0.0529716355144438),
+        vec3<f32>(-0.0980208811401368, 1.15190312990417, -0.0980434501171241),
+        vec3<f32>(-0.0990297440797205, -0.0989611768448433, 1.15107367264116),
+    );
+    let min_ev = -12.47393;
+    let max_ev = 4.026069;
+
+    let log_color = clamp(log2(inset * max(color, vec3<f32>(1e-10))), vec3<f32>(min_ev), vec3<f32>(max_ev));
+    let x = (log_color - min_ev) / (max_ev - min_ev);
+    // sigmoid contrast approximation
+    let x2 = x * x;
+    let x4 = x2 * x2;
+    let curve = 15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x - 0.00232;
+    // the curve produces display-encoded values, decode them for the sRGB target
+    return pow(max(outset * curve, vec3<f32>(0.0)), vec3<f32>(2.2));
+}
+
+[[stage(fragment)]]
+fn main_fs(in: Varyings) -> [[location(0)]] vec4<f32> {
+    let color = textureLoad(source, vec2<i32>(in.clip_pos.xy), 0).xyz * exposure.value;
+    var mapped: vec3<f32>;
+    // has to match `ToneMapping` on the host side
+    switch (i32(params.operator)) {
+        case 1: { mapped = reinhard(color); }
+        case 2: { mapped = agx(color); }
+        default: { mapped = aces(color); }
+    }
+    return vec4<f32>(mapped, 1.0);
+}
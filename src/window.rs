@@ -1,10 +1,15 @@
+use fxhash::FxHashSet;
 use raw_window_handle::{HasRawWindowHandle, RawWindowHandle};
+use std::{path::PathBuf, rc::Rc};
 
 const TARGET_FRAME_TIME: f64 = 1.0 / 120.0;
+/// Logical pixels of the precise scrolling, such as on touchpads,
+/// counted as a line of the mouse wheel.
+const SCROLL_PIXELS_PER_LINE: f64 = 20.0;
 
 pub struct Window {
     event_loop: winit::event_loop::EventLoop<()>,
-    raw: winit::window::Window,
+    raw: Rc<winit::window::Window>,
 }
 
 unsafe impl HasRawWindowHandle for Window {
@@ -23,27 +28,83 @@ impl bc::HasWindow for Window {
     }
 }
 
+/// How the window occupies the screen.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum WindowMode {
+    #[default]
+    Windowed,
+    /// Window without the title bar and the borders.
+    Borderless,
+    /// Window covering the whole monitor it's on,
+    /// without changing the video mode.
+    Fullscreen,
+}
+
 #[derive(Default)]
 pub struct WindowBuilder {
     title: Option<String>,
     size: Option<wgpu::Extent3d>,
+    mode: WindowMode,
+    hide_cursor: bool,
+    grab_cursor: bool,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum Key {
+    /// Digit key of the main row.
     Digit(u8),
+    /// Letter key, in lower case.
     Letter(char),
+    /// Function key, starting with 1 for F1.
     Function(u8),
+    /// Digit key of the numeric keypad.
+    Numpad(u8),
+    /// Punctuation or operator key, as the character it has on a US layout.
+    /// The keypad operators map to the same characters.
+    Symbol(char),
     Up,
     Down,
     Left,
     Right,
     Space,
     Escape,
+    Enter,
+    Tab,
+    Backspace,
+    Insert,
+    Delete,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    /// Either of the Shift keys.
+    Shift,
+    /// Either of the Control keys.
+    Control,
+    /// Either of the Alt keys.
+    Alt,
+    /// Either of the Windows/Command keys.
+    Super,
+    CapsLock,
+    NumLock,
+    ScrollLock,
+    PrintScreen,
+    Pause,
+    Menu,
     Other,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+/// State of the modifier keys.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub control: bool,
+    pub alt: bool,
+    /// Windows/Command key.
+    pub logo: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum Button {
     Left,
     Middle,
@@ -51,27 +112,141 @@ pub enum Button {
     Other(u16),
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TouchPhase {
+    Started,
+    Moved,
+    Ended,
+    Cancelled,
+}
+
+/// Window events. Sizes and positions are in physical pixels,
+/// with the logical positions given along for the pointer and touches.
 pub enum Event {
-    Resize { width: u32, height: u32 },
-    Keyboard { key: Key, pressed: bool },
-    Pointer { position: mint::Vector2<f32> },
-    Scroll { delta: mint::Vector2<f32> },
-    Click { button: Button, pressed: bool },
+    Resize {
+        width: u32,
+        height: u32,
+    },
+    Keyboard {
+        key: Key,
+        pressed: bool,
+    },
+    /// Change of the modifier keys, sent before the key events they affect.
+    Modifiers(Modifiers),
+    /// Character typed, as given by the keyboard layout and the modifiers.
+    /// Repeats while the key is held, and includes control characters,
+    /// such as backspace.
+    Text(char),
+    Pointer {
+        position: mint::Vector2<f32>,
+        /// Position in logical units, i.e. divided by the scale factor.
+        logical: mint::Vector2<f32>,
+    },
+    /// Raw mouse motion, not limited by the window borders,
+    /// which is what mouse-look needs with the cursor grabbed.
+    Motion {
+        delta: mint::Vector2<f32>,
+    },
+    /// Scrolling in lines of the mouse wheel, with the precise
+    /// scrolling of touchpads converted to fractions of lines.
+    Scroll {
+        delta: mint::Vector2<f32>,
+    },
+    Click {
+        button: Button,
+        pressed: bool,
+    },
+    /// Change of the number of physical pixels per logical unit,
+    /// followed by the resize it implies.
+    ScaleFactor(f32),
+    /// Gain or loss of the keyboard focus.
+    Focus(bool),
+    /// File dropped onto the window.
+    FileDropped(PathBuf),
+    Touch {
+        /// Identifier of the finger, unique while it touches.
+        id: u64,
+        phase: TouchPhase,
+        position: mint::Vector2<f32>,
+        logical: mint::Vector2<f32>,
+    },
     Draw,
     Exit,
 }
 
+/// Handle changing the window while the event loop runs.
+#[derive(Clone)]
+pub struct WindowControl {
+    raw: Rc<winit::window::Window>,
+}
+
+impl WindowControl {
+    pub fn set_mode(&self, mode: WindowMode) {
+        apply_mode(&self.raw, mode);
+    }
+
+    pub fn set_cursor_visible(&self, visible: bool) {
+        self.raw.set_cursor_visible(visible);
+    }
+
+    /// Confine the cursor to the window, for mouse-look.
+    /// Not supported on all platforms.
+    pub fn set_cursor_grab(&self, grab: bool) {
+        if let Err(e) = self.raw.set_cursor_grab(grab) {
+            log::warn!("Unable to grab the cursor: {:?}", e);
+        }
+    }
+
+    /// Physical pixels per logical unit.
+    pub fn scale_factor(&self) -> f32 {
+        self.raw.scale_factor() as f32
+    }
+}
+
+fn apply_mode(window: &winit::window::Window, mode: WindowMode) {
+    let fullscreen = match mode {
+        WindowMode::Windowed | WindowMode::Borderless => None,
+        WindowMode::Fullscreen => Some(winit::window::Fullscreen::Borderless(None)),
+    };
+    window.set_fullscreen(fullscreen);
+    window.set_decorations(mode == WindowMode::Windowed);
+}
+
+fn physical_and_logical(
+    position: winit::dpi::PhysicalPosition<f64>,
+    scale_factor: f64,
+) -> (mint::Vector2<f32>, mint::Vector2<f32>) {
+    let logical = position.to_logical::<f64>(scale_factor);
+    (
+        mint::Vector2 {
+            x: position.x as f32,
+            y: position.y as f32,
+        },
+        mint::Vector2 {
+            x: logical.x as f32,
+            y: logical.y as f32,
+        },
+    )
+}
+
 impl Window {
     pub fn new() -> WindowBuilder {
         WindowBuilder::default()
     }
 
+    /// Handle for changing the window from the event loop.
+    pub fn control(&self) -> WindowControl {
+        WindowControl {
+            raw: Rc::clone(&self.raw),
+        }
+    }
+
     pub fn run(self, mut runner: impl 'static + FnMut(Event)) -> ! {
         use std::time;
         use winit::{
             event::{
-                ElementState, Event as WinEvent, KeyboardInput, MouseButton, MouseScrollDelta,
-                VirtualKeyCode as Vkc, WindowEvent,
+                DeviceEvent, ElementState, Event as WinEvent, KeyboardInput, MouseButton,
+                MouseScrollDelta, TouchPhase as WinTouchPhase, WindowEvent,
             },
             event_loop::ControlFlow,
         };
@@ -81,6 +256,7 @@ impl Window {
             event_loop,
             raw: window,
         } = self;
+        let mut scale_factor = window.scale_factor();
 
         event_loop.run(move |event, _, control_flow| {
             *control_flow = match event {
@@ -108,38 +284,94 @@ impl Window {
                     ..
                 } => {
                     runner(Event::Keyboard {
-                        key: if code >= Vkc::Key1 && code <= Vkc::Key0 {
-                            Key::Digit(code as u8 - Vkc::Key1 as u8)
-                        } else if code >= Vkc::A && code <= Vkc::Z {
-                            Key::Letter((code as u8 - Vkc::A as u8) as char)
-                        } else if code >= Vkc::F1 && code <= Vkc::F12 {
-                            Key::Function(code as u8 - Vkc::F1 as u8)
-                        } else {
-                            match code {
-                                Vkc::Left => Key::Left,
-                                Vkc::Right => Key::Right,
-                                Vkc::Up => Key::Up,
-                                Vkc::Down => Key::Down,
-                                Vkc::Space => Key::Space,
-                                Vkc::Escape => Key::Escape,
-                                _ => {
-                                    log::debug!("Unrecognized key {:?}", code);
-                                    Key::Other
-                                }
-                            }
-                        },
+                        key: map_key(code),
                         pressed: state == ElementState::Pressed,
                     });
                     ControlFlow::Poll
                 }
+                WinEvent::WindowEvent {
+                    event: WindowEvent::ModifiersChanged(state),
+                    ..
+                } => {
+                    runner(Event::Modifiers(Modifiers {
+                        shift: state.shift(),
+                        control: state.ctrl(),
+                        alt: state.alt(),
+                        logo: state.logo(),
+                    }));
+                    ControlFlow::Poll
+                }
+                WinEvent::WindowEvent {
+                    event: WindowEvent::ReceivedCharacter(character),
+                    ..
+                } => {
+                    runner(Event::Text(character));
+                    ControlFlow::Poll
+                }
                 WinEvent::WindowEvent {
                     event: WindowEvent::CursorMoved { position, .. },
                     ..
                 } => {
-                    runner(Event::Pointer {
-                        position: mint::Vector2 {
-                            x: position.x as f32,
-                            y: position.y as f32,
+                    let (position, logical) = physical_and_logical(position, scale_factor);
+                    runner(Event::Pointer { position, logical });
+                    ControlFlow::Poll
+                }
+                WinEvent::WindowEvent {
+                    event:
+                        WindowEvent::ScaleFactorChanged {
+                            scale_factor: factor,
+                            new_inner_size,
+                        },
+                    ..
+                } => {
+                    scale_factor = factor;
+                    runner(Event::ScaleFactor(factor as f32));
+                    runner(Event::Resize {
+                        width: new_inner_size.width,
+                        height: new_inner_size.height,
+                    });
+                    ControlFlow::Poll
+                }
+                WinEvent::WindowEvent {
+                    event: WindowEvent::Focused(focused),
+                    ..
+                } => {
+                    runner(Event::Focus(focused));
+                    ControlFlow::Poll
+                }
+                WinEvent::WindowEvent {
+                    event: WindowEvent::DroppedFile(path),
+                    ..
+                } => {
+                    runner(Event::FileDropped(path));
+                    ControlFlow::Poll
+                }
+                WinEvent::WindowEvent {
+                    event: WindowEvent::Touch(touch),
+                    ..
+                } => {
+                    let (position, logical) = physical_and_logical(touch.location, scale_factor);
+                    runner(Event::Touch {
+                        id: touch.id,
+                        phase: match touch.phase {
+                            WinTouchPhase::Started => TouchPhase::Started,
+                            WinTouchPhase::Moved => TouchPhase::Moved,
+                            WinTouchPhase::Ended => TouchPhase::Ended,
+                            WinTouchPhase::Cancelled => TouchPhase::Cancelled,
+                        },
+                        position,
+                        logical,
+                    });
+                    ControlFlow::Poll
+                }
+                WinEvent::DeviceEvent {
+                    event: DeviceEvent::MouseMotion { delta: (x, y) },
+                    ..
+                } => {
+                    runner(Event::Motion {
+                        delta: mint::Vector2 {
+                            x: x as f32,
+                            y: y as f32,
                         },
                     });
                     ControlFlow::Poll
@@ -170,10 +402,11 @@ impl Window {
                             });
                         }
                         MouseScrollDelta::PixelDelta(position) => {
+                            let logical = position.to_logical::<f64>(scale_factor);
                             runner(Event::Scroll {
                                 delta: mint::Vector2 {
-                                    x: position.x as f32,
-                                    y: position.y as f32,
+                                    x: (logical.x / SCROLL_PIXELS_PER_LINE) as f32,
+                                    y: (logical.y / SCROLL_PIXELS_PER_LINE) as f32,
                                 },
                             });
                         }
@@ -210,6 +443,204 @@ impl Window {
     }
 }
 
+/// Input bookkeeping over the window events.
+///
+/// Tracks the held keys and buttons, the pointer, and what changed
+/// since the previous frame. It needs to see every event before it's
+/// handled, and the changes are kept until the next `Event::Draw`
+/// is handled, so they can be checked while drawing.
+#[derive(Debug, Default)]
+pub struct InputState {
+    /// State of the modifier keys.
+    pub modifiers: Modifiers,
+    held_keys: FxHashSet<Key>,
+    pressed_keys: FxHashSet<Key>,
+    released_keys: FxHashSet<Key>,
+    held_buttons: FxHashSet<Button>,
+    pressed_buttons: FxHashSet<Button>,
+    released_buttons: FxHashSet<Button>,
+    pointer: Option<mint::Vector2<f32>>,
+    pointer_delta: glam::Vec2,
+    motion_delta: glam::Vec2,
+    scroll_delta: glam::Vec2,
+    text: String,
+    frame_done: bool,
+}
+
+impl InputState {
+    pub fn update(&mut self, event: &Event) {
+        if self.frame_done {
+            self.frame_done = false;
+            self.pressed_keys.clear();
+            self.released_keys.clear();
+            self.pressed_buttons.clear();
+            self.released_buttons.clear();
+            self.pointer_delta = glam::Vec2::ZERO;
+            self.motion_delta = glam::Vec2::ZERO;
+            self.scroll_delta = glam::Vec2::ZERO;
+            self.text.clear();
+        }
+
+        match *event {
+            Event::Keyboard { key, pressed } => {
+                // repeated presses of a held key are not counted
+                if pressed {
+                    if self.held_keys.insert(key) {
+                        self.pressed_keys.insert(key);
+                    }
+                } else if self.held_keys.remove(&key) {
+                    self.released_keys.insert(key);
+                }
+            }
+            Event::Modifiers(modifiers) => {
+                self.modifiers = modifiers;
+            }
+            Event::Text(character) => {
+                self.text.push(character);
+            }
+            Event::Focus(false) => {
+                // the keys released while unfocused are never reported
+                self.released_keys.extend(self.held_keys.drain());
+                self.released_buttons.extend(self.held_buttons.drain());
+            }
+            Event::Pointer { position, .. } => {
+                if let Some(last) = self.pointer {
+                    self.pointer_delta += glam::Vec2::from(position) - glam::Vec2::from(last);
+                }
+                self.pointer = Some(position);
+            }
+            Event::Motion { delta } => {
+                self.motion_delta += glam::Vec2::from(delta);
+            }
+            Event::Scroll { delta } => {
+                self.scroll_delta += glam::Vec2::from(delta);
+            }
+            Event::Click { button, pressed } => {
+                if pressed {
+                    if self.held_buttons.insert(button) {
+                        self.pressed_buttons.insert(button);
+                    }
+                } else if self.held_buttons.remove(&button) {
+                    self.released_buttons.insert(button);
+                }
+            }
+            Event::Draw => {
+                self.frame_done = true;
+            }
+            _ => {}
+        }
+    }
+
+    pub fn is_key_held(&self, key: Key) -> bool {
+        self.held_keys.contains(&key)
+    }
+    /// Check if the key went down during the frame.
+    pub fn was_key_pressed(&self, key: Key) -> bool {
+        self.pressed_keys.contains(&key)
+    }
+    /// Check if the key went up during the frame.
+    pub fn was_key_released(&self, key: Key) -> bool {
+        self.released_keys.contains(&key)
+    }
+
+    pub fn is_button_held(&self, button: Button) -> bool {
+        self.held_buttons.contains(&button)
+    }
+    /// Check if the button went down during the frame.
+    pub fn was_button_pressed(&self, button: Button) -> bool {
+        self.pressed_buttons.contains(&button)
+    }
+    /// Check if the button went up during the frame.
+    pub fn was_button_released(&self, button: Button) -> bool {
+        self.released_buttons.contains(&button)
+    }
+
+    /// Last known pointer position, if it ever entered the window.
+    pub fn pointer(&self) -> Option<mint::Vector2<f32>> {
+        self.pointer
+    }
+    /// Pointer motion during the frame.
+    pub fn pointer_delta(&self) -> mint::Vector2<f32> {
+        self.pointer_delta.into()
+    }
+    /// Raw mouse motion during the frame, which keeps coming
+    /// when the cursor is grabbed, unlike the pointer motion.
+    pub fn motion_delta(&self) -> mint::Vector2<f32> {
+        self.motion_delta.into()
+    }
+    /// Scrolling during the frame, in lines.
+    pub fn scroll_delta(&self) -> mint::Vector2<f32> {
+        self.scroll_delta.into()
+    }
+    /// Characters typed during the frame.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+}
+
+fn map_key(code: winit::event::VirtualKeyCode) -> Key {
+    use winit::event::VirtualKeyCode as Vkc;
+    if code >= Vkc::Key1 && code <= Vkc::Key9 {
+        Key::Digit(1 + code as u8 - Vkc::Key1 as u8)
+    } else if code >= Vkc::A && code <= Vkc::Z {
+        Key::Letter((b'a' + (code as u8 - Vkc::A as u8)) as char)
+    } else if code >= Vkc::F1 && code <= Vkc::F24 {
+        Key::Function(1 + code as u8 - Vkc::F1 as u8)
+    } else if code >= Vkc::Numpad0 && code <= Vkc::Numpad9 {
+        Key::Numpad(code as u8 - Vkc::Numpad0 as u8)
+    } else {
+        match code {
+            Vkc::Key0 => Key::Digit(0),
+            Vkc::Left => Key::Left,
+            Vkc::Right => Key::Right,
+            Vkc::Up => Key::Up,
+            Vkc::Down => Key::Down,
+            Vkc::Space => Key::Space,
+            Vkc::Escape => Key::Escape,
+            Vkc::Return | Vkc::NumpadEnter => Key::Enter,
+            Vkc::Tab => Key::Tab,
+            Vkc::Back => Key::Backspace,
+            Vkc::Insert => Key::Insert,
+            Vkc::Delete => Key::Delete,
+            Vkc::Home => Key::Home,
+            Vkc::End => Key::End,
+            Vkc::PageUp => Key::PageUp,
+            Vkc::PageDown => Key::PageDown,
+            Vkc::LShift | Vkc::RShift => Key::Shift,
+            Vkc::LControl | Vkc::RControl => Key::Control,
+            Vkc::LAlt | Vkc::RAlt => Key::Alt,
+            Vkc::LWin | Vkc::RWin => Key::Super,
+            Vkc::Capital => Key::CapsLock,
+            Vkc::Numlock => Key::NumLock,
+            Vkc::Scroll => Key::ScrollLock,
+            Vkc::Snapshot | Vkc::Sysrq => Key::PrintScreen,
+            Vkc::Pause => Key::Pause,
+            Vkc::Apps => Key::Menu,
+            Vkc::Apostrophe => Key::Symbol('\''),
+            Vkc::Asterisk | Vkc::NumpadMultiply => Key::Symbol('*'),
+            Vkc::At => Key::Symbol('@'),
+            Vkc::Backslash => Key::Symbol('\\'),
+            Vkc::Caret => Key::Symbol('^'),
+            Vkc::Colon => Key::Symbol(':'),
+            Vkc::Comma | Vkc::NumpadComma => Key::Symbol(','),
+            Vkc::Equals | Vkc::NumpadEquals => Key::Symbol('='),
+            Vkc::Grave => Key::Symbol('`'),
+            Vkc::LBracket => Key::Symbol('['),
+            Vkc::Minus | Vkc::NumpadSubtract => Key::Symbol('-'),
+            Vkc::Period | Vkc::NumpadDecimal => Key::Symbol('.'),
+            Vkc::Plus | Vkc::NumpadAdd => Key::Symbol('+'),
+            Vkc::RBracket => Key::Symbol(']'),
+            Vkc::Semicolon => Key::Symbol(';'),
+            Vkc::Slash | Vkc::NumpadDivide => Key::Symbol('/'),
+            Vkc::Underline => Key::Symbol('_'),
+            _ => {
+                log::debug!("Unrecognized key {:?}", code);
+                Key::Other
+            }
+        }
+    }
+}
+
 impl WindowBuilder {
     pub fn title(self, title: &str) -> Self {
         Self {
@@ -229,6 +660,25 @@ impl WindowBuilder {
         }
     }
 
+    pub fn mode(self, mode: WindowMode) -> Self {
+        Self { mode, ..self }
+    }
+
+    pub fn hide_cursor(self) -> Self {
+        Self {
+            hide_cursor: true,
+            ..self
+        }
+    }
+
+    /// Confine the cursor to the window, see `WindowControl::set_cursor_grab`.
+    pub fn grab_cursor(self) -> Self {
+        Self {
+            grab_cursor: true,
+            ..self
+        }
+    }
+
     pub fn build(self) -> Window {
         let event_loop = winit::event_loop::EventLoop::new();
         let mut builder = winit::window::WindowBuilder::new()
@@ -240,7 +690,18 @@ impl WindowBuilder {
             builder = builder
                 .with_inner_size(winit::dpi::Size::Logical((size.width, size.height).into()));
         }
-        let raw = builder.build(&event_loop).unwrap();
-        Window { raw, event_loop }
+        let raw = Rc::new(builder.build(&event_loop).unwrap());
+        let window = Window { raw, event_loop };
+        let control = window.control();
+        if self.mode != WindowMode::Windowed {
+            control.set_mode(self.mode);
+        }
+        if self.hide_cursor {
+            control.set_cursor_visible(false);
+        }
+        if self.grab_cursor {
+            control.set_cursor_grab(true);
+        }
+        window
     }
 }
@@ -1,6 +1,10 @@
 use naga::{front::wgsl, valid::Validator};
 use std::{fs, path::PathBuf};
 
+#[path = "../src/pass/multiview.rs"]
+mod multiview;
+use multiview::{INSTANCE_VIEW, MULTIVIEW_VIEW};
+
 /// Runs through all pass shaders and ensures they are valid WGSL.
 #[test]
 fn parse_wgsl() {
@@ -25,16 +29,24 @@ fn parse_wgsl() {
             }
         };
 
-        let module = match wgsl::parse_str(&shader) {
-            Ok(module) => module,
-            Err(e) => panic!("{}", e.emit_to_string(&shader)),
-        };
-        //TODO: re-use the validator
-        Validator::new(
-            naga::valid::ValidationFlags::all(),
-            naga::valid::Capabilities::empty(),
-        )
-        .validate(&module)
-        .unwrap();
+        validate(&shader);
+        // the passes supporting stereo also have a multiview variant
+        if shader.contains(INSTANCE_VIEW) {
+            validate(&shader.replace(INSTANCE_VIEW, MULTIVIEW_VIEW));
+        }
     }
 }
+
+fn validate(shader: &str) {
+    let module = match wgsl::parse_str(shader) {
+        Ok(module) => module,
+        Err(e) => panic!("{}", e.emit_to_string(shader)),
+    };
+    //TODO: re-use the validator
+    Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::empty(),
+    )
+    .validate(&module)
+    .unwrap();
+}